@@ -0,0 +1,149 @@
+//! Attachment filtering by file extension, size and content type
+
+use serenity::model::prelude::{Attachment, ChannelId};
+
+use crate::config::AttachmentSettings;
+
+/// The largest size limit that can be set, in kilobytes (1GB)
+pub const MAX_SIZE: u64 = 1_024 * 1_024;
+
+/// Check a message's attachments. Returns why an attachment isn't allowed.
+pub fn check(
+    channel: &ChannelId,
+    attachments: &[Attachment],
+    settings: &AttachmentSettings,
+) -> Option<String>
+{
+    let rules = settings.channels.iter().find(|c| c.channel == *channel);
+    let max_size = match rules {
+        Some(c) if c.max_size > 0 => c.max_size,
+        _ => settings.max_size,
+    };
+
+    for attachment in attachments {
+        let name = &attachment.filename;
+        if let Some(extension) = extension(name) {
+            let denied = settings
+                .denied_extensions
+                .iter()
+                .chain(rules.iter().flat_map(|c| c.denied_extensions.iter()))
+                .any(|e| normalize_extension(e) == extension);
+            if denied {
+                return Some(format!(
+                    "`{name}` has a denied file extension (.{extension})"
+                ));
+            }
+        }
+
+        if let Some(content_type) = &attachment.content_type {
+            let content_type = content_type.to_lowercase();
+            if let Some(denied) = settings
+                .denied_content_types
+                .iter()
+                .find(|t| type_matches(&content_type, t))
+            {
+                return Some(format!("`{name}` has a denied content type ({denied})"));
+            }
+        }
+
+        // Limits set in the settings file aren't bound by `MAX_SIZE`
+        if max_size > 0 && attachment.size > max_size.saturating_mul(1024) {
+            return Some(format!(
+                "`{name}` is {}KB, larger than the limit of {max_size}KB",
+                attachment.size / 1024
+            ));
+        }
+    }
+
+    None
+}
+
+/// The lowercase extension of a file name, ignoring trailing dots and spaces
+/// that Windows drops
+fn extension(name: &str) -> Option<String>
+{
+    let name = name.trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    name.rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .filter(|e| !e.is_empty())
+}
+
+/// An extension as entered in the settings, without its dot
+pub fn normalize_extension(extension: &str) -> String
+{
+    extension.trim().trim_start_matches('.').to_lowercase()
+}
+
+/// Whether a content type matches a denied type, which may cover every
+/// subtype (e.g. `video/*`)
+fn type_matches(content_type: &str, denied: &str) -> bool
+{
+    let denied = denied.trim().to_lowercase();
+    let denied = denied.trim_end_matches('*');
+    // Parameters such as `; charset=utf-8` don't change the type
+    let content_type = content_type.split(';').next().unwrap_or("").trim();
+
+    if denied.ends_with('/') {
+        content_type.starts_with(denied)
+    } else {
+        content_type == denied
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_type_matches()
+    {
+        assert!(type_matches("video/mp4", "video/*"));
+        assert!(type_matches("video/mp4", "Video/"));
+        assert!(type_matches(
+            "application/x-msdownload",
+            "application/x-msdownload"
+        ));
+        assert!(type_matches("text/html; charset=utf-8", "text/html"));
+        assert!(!type_matches("text/plain", "text/html"));
+        assert!(!type_matches("videos/mp4", "video/*"));
+        assert!(!type_matches("image/png", "*"));
+    }
+
+    #[test]
+    fn test_max_size()
+    {
+        let attachment: Attachment = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "filename": "big.png",
+            "size": 5_000,
+            "url": "",
+            "proxy_url": "",
+        }))
+        .unwrap();
+        let attachments = [attachment];
+        let channel = ChannelId(1);
+        let mut settings = AttachmentSettings {
+            max_size: 4,
+            ..Default::default()
+        };
+        assert!(check(&channel, &attachments, &settings).is_some());
+
+        settings.max_size = 5;
+        assert!(check(&channel, &attachments, &settings).is_none());
+
+        settings.max_size = u64::MAX;
+        assert!(check(&channel, &attachments, &settings).is_none());
+    }
+
+    #[test]
+    fn test_extension()
+    {
+        assert_eq!(extension("Setup.EXE").as_deref(), Some("exe"));
+        assert_eq!(extension("evil.exe. . ").as_deref(), Some("exe"));
+        assert_eq!(extension("archive.tar.gz").as_deref(), Some("gz"));
+        assert_eq!(extension("README"), None);
+        assert_eq!(extension("trailing."), None);
+        assert_eq!(normalize_extension(" .EXE"), "exe");
+    }
+}
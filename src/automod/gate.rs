@@ -0,0 +1,91 @@
+//! Account age and avatar requirements for new members
+
+use log::{error, info};
+use serenity::{
+    model::{prelude::Member, timestamp::Timestamp},
+    prelude::Context,
+    utils::Colour,
+};
+
+use crate::{
+    builtins::{
+        moderation::{self, Moderator},
+        settings,
+    },
+    config::LogCategory,
+    logging,
+};
+
+/// Check a new member against the guild's requirements, kicking or
+/// quarantining them if they fail. Returns whether the member was acted on.
+pub async fn member_joined(context: &Context, member: &Member) -> bool
+{
+    let gid = member.guild_id;
+    let settings = settings::get(&gid);
+    let gate = &settings.gate;
+    let user = &member.user;
+
+    // Discord ids embed when the account was made
+    let created = user.id.created_at();
+    let age = Timestamp::now().unix_timestamp() - created.unix_timestamp();
+    let min_age = gate.min_account_age.duration().num_seconds();
+
+    let reason = if min_age > 0 && age < min_age {
+        format!(
+            "Account is too new (made <t:{}:R>, the minimum age is {})",
+            created.unix_timestamp(),
+            gate.min_account_age
+        )
+    } else if gate.require_avatar && user.avatar.is_none() {
+        "Account has no avatar".to_string()
+    } else {
+        return false;
+    };
+    info!(
+        "'{}' failed the join requirements of '{gid}': {reason}",
+        user.name
+    );
+
+    let result = if settings.shadow_mode {
+        "shadow mode, not taken".to_string()
+    } else if let Some(role) = gate.quarantine_role {
+        match member.clone().add_role(&context.http, role).await {
+            Ok(_) => format!("Quarantined with <@&{role}>"),
+            Err(e) => {
+                error!("Error quarantining '{}': {:?}", user.id, e);
+                format!("Error quarantining: {e}")
+            }
+        }
+    } else {
+        // Explain the kick first, since the member can't be messaged after
+        let guild = match gid.to_partial_guild(&context.http).await {
+            Ok(g) => g.name,
+            Err(_) => "a server".to_string(),
+        };
+        let dm = format!(
+            "You were removed from {guild} because your account doesn't meet its requirements: \
+             {}. You're welcome to join again once it does.",
+            reason.to_lowercase()
+        );
+        if let Err(e) = user.direct_message(&context.http, |m| m.content(dm)).await {
+            error!("Error messaging '{}': {:?}", user.id, e);
+        }
+
+        moderation::kick::run(context, &gid, user, reason.clone(), &Moderator::Automod).await
+    };
+
+    logging::log(
+        context,
+        &gid,
+        LogCategory::Automod,
+        "Join requirements",
+        format!(
+            "{} ({}) failed the join requirements.\n{reason}\nAction: {result}",
+            user.tag(),
+            user.id
+        ),
+        Colour::ORANGE,
+    )
+    .await;
+    true
+}
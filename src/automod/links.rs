@@ -0,0 +1,307 @@
+//! Link and invite filtering
+//!
+//! Links are checked against a guild's allowed and denied domains, and the
+//! phishing domain list at `resources.phishingDomains`. The list is reloaded
+//! when the file changes, checked at most once a minute, and when the config
+//! is reloaded.
+//!
+//! Invites are resolved to see which server they're for. Resolved invites are
+//! cached, and only a few are looked up per message.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, read_to_string},
+    io::Error,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use lazy_static::lazy_static;
+use log::{error, info};
+use regex::Regex;
+use serenity::{
+    model::prelude::{GuildId, Invite},
+    prelude::Context,
+};
+
+use crate::{config::LinkSettings, reload};
+
+lazy_static! {
+    static ref URL: Regex = Regex::new(
+        r"(?i)(?:https?://)?((?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+[a-z]{2,})(?::\d+)?(?:/\S*)?"
+    )
+    .unwrap();
+    static ref INVITE: Regex =
+        Regex::new(r"(?i)(?:discord(?:app)?\.com/invite|discord\.gg)/([a-z0-9-]+)").unwrap();
+    static ref PHISHING: RwLock<PhishingList> = RwLock::new(PhishingList::default());
+    static ref INVITES: Mutex<HashMap<String, CachedInvite>> = Mutex::new(HashMap::new());
+}
+
+/// How long a resolved invite is remembered
+const INVITE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long an invite that couldn't be resolved is remembered
+const FAILED_INVITE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The most invites remembered at once, across every guild
+const MAX_CACHED_INVITES: usize = 1_000;
+
+/// The most uncached invites looked up for one message
+const MAX_INVITE_LOOKUPS: usize = 3;
+
+/// How often the phishing list's file is checked for changes
+const PHISHING_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Known phishing domains
+#[derive(Default)]
+struct PhishingList
+{
+    /// When the file was last modified, as of the last load
+    modified: Option<SystemTime>,
+
+    /// When the file was last checked for changes
+    checked: Option<Instant>,
+    domains: HashSet<String>,
+}
+
+/// The server an invite is for
+struct CachedInvite
+{
+    guild: Option<GuildId>,
+    expires: Instant,
+}
+
+/// Check a message's links. Returns why a link isn't allowed.
+pub async fn check(
+    context: &Context,
+    gid: &GuildId,
+    content: &str,
+    settings: &LinkSettings,
+) -> Option<String>
+{
+    for domain in domains(content) {
+        if settings
+            .allowed_domains
+            .iter()
+            .any(|d| is_subdomain(&domain, d))
+        {
+            continue;
+        }
+
+        if settings
+            .denied_domains
+            .iter()
+            .any(|d| is_subdomain(&domain, d))
+        {
+            return Some(format!("Link to denied domain `{domain}`"));
+        }
+
+        if settings.block_phishing && is_phishing(&domain) {
+            return Some(format!("Link to phishing domain `{domain}`"));
+        }
+    }
+
+    if settings.block_invites {
+        let mut codes = invites(content);
+        codes.sort_unstable();
+        codes.dedup();
+
+        let mut lookups = 0;
+        for code in codes {
+            let guild = match cached_invite(&code) {
+                Some(x) => x,
+                None if lookups < MAX_INVITE_LOOKUPS => {
+                    lookups += 1;
+                    resolve_invite(context, &code).await
+                }
+                // Invites past the limit are treated as foreign, so a message
+                // full of invites can't cause a flood of requests
+                None => None,
+            };
+
+            if guild != Some(*gid) {
+                return Some(format!("Invite to another server (`{code}`)"));
+            }
+        }
+    }
+
+    None
+}
+
+/// The server of a cached invite, if it's cached
+fn cached_invite(code: &str) -> Option<Option<GuildId>>
+{
+    let invites = INVITES.lock().unwrap();
+    invites
+        .get(code)
+        .filter(|i| i.expires > Instant::now())
+        .map(|i| i.guild)
+}
+
+/// Look up the server an invite is for, and cache it. Invites that can't be
+/// resolved are treated as foreign.
+async fn resolve_invite(context: &Context, code: &str) -> Option<GuildId>
+{
+    let (guild, ttl) = match Invite::get(&context.http, code, false, false, None).await {
+        Ok(invite) => (invite.guild.map(|g| g.id), INVITE_TTL),
+        Err(_) => (None, FAILED_INVITE_TTL),
+    };
+
+    let now = Instant::now();
+    let mut invites = INVITES.lock().unwrap();
+    if invites.len() >= MAX_CACHED_INVITES {
+        invites.retain(|_, i| i.expires > now);
+        if invites.len() >= MAX_CACHED_INVITES {
+            invites.clear();
+        }
+    }
+    invites.insert(
+        code.to_string(),
+        CachedInvite {
+            guild,
+            expires: now + ttl,
+        },
+    );
+    guild
+}
+
+/// The lowercase domains linked in some text
+pub fn domains(content: &str) -> Vec<String>
+{
+    URL.captures_iter(content)
+        .map(|c| c[1].trim_end_matches('.').to_lowercase())
+        .collect()
+}
+
+/// The invite codes in some text
+pub fn invites(content: &str) -> Vec<String>
+{
+    INVITE
+        .captures_iter(content)
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+/// Whether `domain` is `parent` or one of its subdomains
+fn is_subdomain(domain: &str, parent: &str) -> bool
+{
+    let parent = parent.trim().trim_start_matches("*.").to_lowercase();
+    domain == parent || domain.ends_with(&format!(".{parent}"))
+}
+
+/// Whether a domain, or a domain it's under, is a known phishing domain
+pub fn is_phishing(domain: &str) -> bool
+{
+    refresh_phishing();
+
+    let list = PHISHING.read().unwrap();
+    let mut domain = domain;
+    loop {
+        if list.domains.contains(domain) {
+            return true;
+        }
+        match domain.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => domain = parent,
+            _ => return false,
+        }
+    }
+}
+
+/// Reload the phishing list if its file changed since it was last loaded. The
+/// file is checked at most once per `PHISHING_CHECK_INTERVAL`.
+fn refresh_phishing()
+{
+    let now = Instant::now();
+    if let Some(checked) = PHISHING.read().unwrap().checked {
+        if now.duration_since(checked) < PHISHING_CHECK_INTERVAL {
+            return;
+        }
+    }
+    PHISHING.write().unwrap().checked = Some(now);
+
+    let path = reload::get().resources.phishing_domains.clone();
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    if modified.is_none() || PHISHING.read().unwrap().modified == modified {
+        return;
+    }
+
+    if let Err(e) = reload_phishing() {
+        error!(
+            "Couldn't load phishing domains from '{}': {e}",
+            path.display()
+        );
+    }
+}
+
+/// Load the phishing list from disk. Returns the number of domains loaded.
+pub fn reload_phishing() -> Result<usize, Error>
+{
+    let path = reload::get().resources.phishing_domains.clone();
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    let contents = read_to_string(&path)?;
+
+    let domains: HashSet<String> = contents
+        .lines()
+        .map(|l| {
+            l.trim()
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .trim_start_matches("*.")
+                .trim_end_matches('/')
+                .to_lowercase()
+        })
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+
+    let count = domains.len();
+    *PHISHING.write().unwrap() = PhishingList {
+        modified,
+        checked: Some(Instant::now()),
+        domains,
+    };
+    info!("Loaded {count} phishing domains from '{}'", path.display());
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_domains()
+    {
+        assert_eq!(
+            domains("see https://Example.com/path?q=1 and www.foo.co.uk."),
+            vec!["example.com", "www.foo.co.uk"]
+        );
+        assert_eq!(
+            domains("http://sub.example.org:8080"),
+            vec!["sub.example.org"]
+        );
+        assert!(domains("no links here, just text.").is_empty());
+        assert!(domains("version 1.2.3").is_empty());
+    }
+
+    #[test]
+    fn test_invites()
+    {
+        assert_eq!(
+            invites("join discord.gg/abc or https://discord.com/invite/XyZ-1"),
+            vec!["abc", "XyZ-1"]
+        );
+        assert_eq!(invites("discordapp.com/invite/old"), vec!["old"]);
+        assert!(invites("https://discord.com/channels/1/2").is_empty());
+    }
+
+    #[test]
+    fn test_is_subdomain()
+    {
+        assert!(is_subdomain("example.com", "example.com"));
+        assert!(is_subdomain("a.b.example.com", "example.com"));
+        assert!(is_subdomain("cdn.example.com", "*.Example.com "));
+        assert!(!is_subdomain("badexample.com", "example.com"));
+        assert!(!is_subdomain("example.com.evil.net", "example.com"));
+        assert!(!is_subdomain("example.com", "cdn.example.com"));
+    }
+}
@@ -0,0 +1,117 @@
+//! Mass mention protection
+
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+use log::error;
+use regex::Regex;
+use serenity::{
+    model::{
+        prelude::{GuildId, Message, RoleId, UserId},
+        Permissions,
+    },
+    prelude::Context,
+};
+
+use crate::config::MentionSettings;
+
+lazy_static! {
+    static ref MENTION: Regex = Regex::new(r"<@[!&]?\d+>").unwrap();
+}
+
+/// Check a message for too many mentions, or for `@everyone` from a member
+/// who can't use it. Returns why the message broke the rule.
+pub async fn check(
+    context: &Context,
+    gid: &GuildId,
+    msg: &Message,
+    roles: &[RoleId],
+    settings: &MentionSettings,
+) -> Option<String>
+{
+    if roles.iter().any(|r| settings.exempt_roles.contains(r)) {
+        return None;
+    }
+
+    if settings.max_mentions > 0 {
+        let users: HashSet<UserId> = msg.mentions.iter().map(|u| u.id).collect();
+        let mentioned_roles: HashSet<&RoleId> = msg.mention_roles.iter().collect();
+        let count = users.len() + mentioned_roles.len();
+        if count > settings.max_mentions {
+            return Some(format!(
+                "Mentioned {count} users and roles (limit {})",
+                settings.max_mentions
+            ));
+        }
+    }
+
+    if settings.block_everyone
+        && (msg.content.contains("@everyone") || msg.content.contains("@here"))
+        && !may_mention_everyone(context, gid, &msg.author.id, roles).await
+    {
+        return Some("Tried to mention @everyone without permission".to_string());
+    }
+
+    None
+}
+
+/// Whether a member is allowed to mention `@everyone`. Assumes they are when
+/// the guild can't be fetched.
+async fn may_mention_everyone(
+    context: &Context,
+    gid: &GuildId,
+    uid: &UserId,
+    roles: &[RoleId],
+) -> bool
+{
+    let guild = match gid.to_partial_guild(&context.http).await {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Error fetching guild '{gid}': {:?}", e);
+            return true;
+        }
+    };
+
+    if guild.owner_id == *uid {
+        return true;
+    }
+
+    // The @everyone role shares the guild's id
+    guild
+        .roles
+        .iter()
+        .filter(|(id, _)| id.0 == gid.0 || roles.contains(id))
+        .any(|(_, role)| {
+            role.permissions
+                .intersects(Permissions::MENTION_EVERYONE | Permissions::ADMINISTRATOR)
+        })
+}
+
+/// Check some text the way a message would be checked, without knowing who
+/// sent it. Returns why the text would break the rule.
+pub fn check_text(content: &str, settings: &MentionSettings) -> Option<String>
+{
+    if settings.max_mentions > 0 {
+        let mentions: HashSet<&str> = MENTION
+            .find_iter(content)
+            .map(|m| {
+                m.as_str()
+                    .trim_start_matches("<@!")
+                    .trim_start_matches("<@")
+            })
+            .collect();
+        if mentions.len() > settings.max_mentions {
+            return Some(format!(
+                "Mentions {} users and roles (limit {})",
+                mentions.len(),
+                settings.max_mentions
+            ));
+        }
+    }
+
+    if settings.block_everyone && (content.contains("@everyone") || content.contains("@here")) {
+        return Some("Mentions @everyone, if the author may not".to_string());
+    }
+
+    None
+}
@@ -0,0 +1,342 @@
+//! Automatic moderation of guild messages
+
+pub mod attachments;
+pub mod gate;
+pub mod links;
+pub mod mentions;
+pub mod names;
+pub mod raid;
+pub mod spam;
+
+use log::{error, info};
+use serenity::{
+    model::prelude::{GuildId, Message, RoleId},
+    prelude::Context,
+    utils::{Colour, MessageBuilder},
+};
+
+use crate::{
+    builtins::{
+        moderation::{self, Moderator},
+        settings,
+    },
+    cache,
+    config::{AutomodAction, AutomodRule, LogCategory},
+    filtering, logging,
+};
+
+/// The most matching rules listed by `/automod test`
+const MAX_REPORTED_HITS: usize = 5;
+
+/// An automod rule that matched a message
+#[derive(Debug, Clone)]
+pub struct Hit
+{
+    /// The kind of rule that matched (e.g. "Restricted word")
+    pub rule: &'static str,
+
+    /// What matched
+    pub reason: String,
+
+    /// What to do about it
+    pub action: AutomodAction,
+}
+
+/// How a checked message came about
+#[derive(Debug, Clone)]
+pub enum Event
+{
+    /// The message was sent
+    Sent,
+
+    /// The message was edited. Holds its content before the edit, if it
+    /// was cached.
+    Edited(Option<String>),
+}
+
+/// Run the automod rules against a new guild message
+pub async fn check_message(context: &Context, msg: &Message)
+{
+    check(context, msg, Event::Sent).await
+}
+
+/// Run the automod rules against the new content of an edited guild message
+pub async fn check_edit(context: &Context, msg: &Message, before: Option<String>)
+{
+    check(context, msg, Event::Edited(before)).await
+}
+
+async fn check(context: &Context, msg: &Message, event: Event)
+{
+    let gid = match msg.guild_id {
+        Some(x) => x,
+        None => return,
+    };
+
+    let settings = settings::get(&gid);
+    let mentions = &settings.mentions;
+    let check_mentions = mentions.max_mentions > 0 || mentions.block_everyone;
+
+    // Only fetch the author's roles when something depends on them
+    let roles = if check_mentions || settings.exemptions.has_roles() {
+        author_roles(context, &gid, msg).await
+    } else {
+        Vec::new()
+    };
+    let exempt = |rule| settings.exemptions.is_exempt(rule, &msg.channel_id, &roles);
+
+    // Every new message counts towards flood detection, so track it first.
+    // Edits don't count.
+    let flood =
+        if settings.spam.enabled && matches!(event, Event::Sent) && !exempt(AutomodRule::Spam) {
+            spam::check(&gid, msg, &settings.spam)
+        } else {
+            None
+        };
+
+    // Check the global restricted words along with the guild's own
+    let mut hit = None;
+    if !exempt(AutomodRule::Words) {
+        hit = filtering::guild_matcher(&gid, &settings)
+            .find(&msg.content)
+            .map(|m| {
+                Hit {
+                    rule: "Restricted word",
+                    reason: format!("Rule: `{}`\nMatched: `{}`", m.rule, m.matched),
+                    action: m.action(),
+                }
+            });
+    }
+
+    if hit.is_none() && !exempt(AutomodRule::Links) {
+        if let Some(reason) = links::check(context, &gid, &msg.content, &settings.links).await {
+            hit = Some(Hit {
+                rule: "Link",
+                reason,
+                action: settings.links.action,
+            });
+        }
+    }
+
+    if hit.is_none() && !msg.attachments.is_empty() && !exempt(AutomodRule::Attachments) {
+        let attachments = &settings.attachments;
+        if let Some(reason) = attachments::check(&msg.channel_id, &msg.attachments, attachments) {
+            hit = Some(Hit {
+                rule: "Attachment",
+                reason,
+                action: attachments.action,
+            });
+        }
+    }
+
+    if hit.is_none() && check_mentions && !exempt(AutomodRule::Mentions) {
+        if let Some(reason) = mentions::check(context, &gid, msg, &roles, mentions).await {
+            hit = Some(Hit {
+                rule: "Mass mention",
+                reason,
+                action: AutomodAction::Timeout(mentions.timeout),
+            });
+        }
+    }
+
+    // Warn for a first flood, then timeout for longer each time
+    if hit.is_none() {
+        hit = flood.map(|f| {
+            Hit {
+                rule: "Spam",
+                reason: format!("{} (offence {})", f.reason, f.strikes),
+                action: match f.timeout(settings.spam.timeout) {
+                    Some(t) => AutomodAction::Timeout(t),
+                    None => AutomodAction::Warn,
+                },
+            }
+        });
+    }
+
+    if let Some(hit) = hit {
+        enforce(context, &gid, msg, hit, &event).await;
+    }
+}
+
+/// Run the automod rules against some text without acting on it. Returns a
+/// report of the normalized text, every rule that matched and what would be
+/// done.
+pub async fn test(context: &Context, gid: &GuildId, text: &str) -> String
+{
+    let settings = settings::get(gid);
+    let mut hits = Vec::new();
+
+    let (plain, leet) = filtering::normalize(text);
+    for m in filtering::guild_matcher(gid, &settings).find_all(text) {
+        hits.push(Hit {
+            rule: "Restricted word",
+            reason: format!("`{}` matched `{}`", m.rule, m.matched),
+            action: m.action(),
+        });
+    }
+
+    if let Some(reason) = links::check(context, gid, text, &settings.links).await {
+        hits.push(Hit {
+            rule: "Link",
+            reason,
+            action: settings.links.action,
+        });
+    }
+
+    if let Some(reason) = mentions::check_text(text, &settings.mentions) {
+        hits.push(Hit {
+            rule: "Mass mention",
+            reason,
+            action: AutomodAction::Timeout(settings.mentions.timeout),
+        });
+    }
+
+    let mut report = format!(
+        "Normalized: `{}`\nWith leetspeak: `{}`\n\n",
+        logging::shorten(&plain, 200),
+        logging::shorten(&leet, 200)
+    );
+    if hits.is_empty() {
+        report.push_str("No rules matched.");
+    } else {
+        // Keep the report within Discord's message length
+        for hit in hits.iter().take(MAX_REPORTED_HITS) {
+            report.push_str(&format!(
+                "{}: {} ({})\n",
+                hit.rule,
+                logging::shorten(&hit.reason, 150),
+                hit.action
+            ));
+        }
+        if hits.len() > MAX_REPORTED_HITS {
+            report.push_str(&format!("...and {} more\n", hits.len() - MAX_REPORTED_HITS));
+        }
+        report.push_str(&format!("\nAction: {}", hits[0].action));
+        if settings.shadow_mode {
+            report.push_str(" (shadow mode is on, so it would only be logged)");
+        }
+    }
+
+    report.push_str(
+        "\n\nFlood detection, attachments, and channel and role exemptions depend on the \
+         message's author, files and channel, so they aren't tested.",
+    );
+    report
+}
+
+/// The roles of a message's author
+async fn author_roles(context: &Context, gid: &GuildId, msg: &Message) -> Vec<RoleId>
+{
+    match &msg.member {
+        Some(member) => member.roles.clone(),
+        None => {
+            match gid.member(&context.http, msg.author.id).await {
+                Ok(member) => member.roles,
+                Err(e) => {
+                    error!("Error fetching member '{}': {:?}", msg.author.id, e);
+                    Vec::new()
+                }
+            }
+        }
+    }
+}
+
+/// Carry out a hit's action against a message and its author
+pub async fn enforce(context: &Context, gid: &GuildId, msg: &Message, hit: Hit, event: &Event)
+{
+    info!(
+        "Automod: {} from '{}' in '{gid}' ({})",
+        hit.rule, msg.author.name, hit.action
+    );
+
+    // In shadow mode, hits are only logged
+    let shadow = settings::get(gid).shadow_mode;
+
+    if hit.action.deletes() && !shadow {
+        // Automod logs its own deletions
+        cache::remove(&msg.id);
+        if let Err(why) = msg.delete(&context.http).await {
+            error!("Error removing message: {:?}", why);
+        }
+
+        let response = MessageBuilder::new()
+            .mention(&msg.author)
+            .push(format!(
+                " Your message was removed ({})",
+                hit.rule.to_lowercase()
+            ))
+            .build();
+        if let Err(why) = msg.channel_id.say(&context.http, &response).await {
+            error!("Error sending message: {:?}", why);
+        }
+    }
+
+    // Punish the author. Warnings go through the guild's escalation ladder.
+    let reason = format!("Automod: {}", hit.rule.to_lowercase());
+    let result = match hit.action {
+        _ if shadow => Some("shadow mode, not taken".to_string()),
+        AutomodAction::Log | AutomodAction::Delete => None,
+        AutomodAction::Warn => {
+            Some(
+                moderation::warn::warn(
+                    context,
+                    gid,
+                    msg.author.clone(),
+                    reason,
+                    1,
+                    &Moderator::Automod,
+                )
+                .await,
+            )
+        }
+        AutomodAction::Timeout(duration) => {
+            moderation::timeout::timeout(
+                context,
+                gid,
+                msg.author.clone(),
+                duration,
+                &Moderator::Automod,
+                Some(&reason),
+            )
+            .await
+        }
+        AutomodAction::Kick => {
+            Some(
+                moderation::kick::run(context, gid, &msg.author, reason, &Moderator::Automod).await,
+            )
+        }
+    };
+
+    let mut description = format!(
+        "{} in <#{}> by {}.\n{}\nAction: {}",
+        hit.rule,
+        msg.channel_id,
+        msg.author.tag(),
+        logging::shorten(&hit.reason, 300),
+        hit.action
+    );
+    if let Some(result) = result {
+        description.push_str(&format!(" ({})", logging::shorten(&result, 300)));
+    }
+    let content = logging::shorten(&msg.content, logging::MAX_CONTENT);
+    match event {
+        Event::Sent => description.push_str(&format!("\n\n{content}")),
+        Event::Edited(Some(before)) => {
+            description.push_str(&format!(
+                "\n\nBefore the edit:\n{}\n\nAfter the edit:\n{content}",
+                logging::shorten(before, logging::MAX_CONTENT)
+            ))
+        }
+        Event::Edited(None) => description.push_str(&format!("\n\nAfter an edit:\n{content}")),
+    }
+
+    logging::log(
+        context,
+        gid,
+        LogCategory::Automod,
+        "Automod",
+        description,
+        Colour::ORANGE,
+    )
+    .await;
+}
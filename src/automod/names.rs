@@ -0,0 +1,130 @@
+//! Nickname and username screening
+
+use log::{error, info};
+use serenity::{
+    model::prelude::{GuildId, User},
+    prelude::Context,
+    utils::Colour,
+};
+
+use crate::{
+    builtins::settings,
+    config::{LogCategory, NameSettings},
+    filtering, logging,
+};
+
+/// The longest nickname Discord allows, in characters
+const MAX_NICKNAME: usize = 32;
+
+/// Screen a member's display name, changing their nickname when it contains a
+/// restricted word or is hoisted
+pub async fn check(context: &Context, gid: &GuildId, user: &User, nick: Option<&str>)
+{
+    let settings = settings::get(gid);
+    let names = &settings.names;
+    if !names.filter && !names.dehoist {
+        return;
+    }
+
+    let current = nick.unwrap_or(&user.name);
+    let (new, reason) = if let Some(m) = names
+        .filter
+        .then(|| filtering::guild_matcher(gid, &settings).find(current))
+        .flatten()
+    {
+        (
+            safe_nickname(names, user),
+            format!("Restricted word `{}` matched `{}`", m.rule, m.matched),
+        )
+    } else if names.dehoist && is_hoisted(current) {
+        let dehoisted = current.trim_start_matches(is_hoisting).trim();
+        let new = if dehoisted.is_empty() {
+            safe_nickname(names, user)
+        } else {
+            dehoisted.to_string()
+        };
+        (new, "Name starts with sorting characters".to_string())
+    } else {
+        return;
+    };
+
+    if new == current {
+        return;
+    }
+
+    info!("Renaming '{}' in '{gid}' to '{new}'", user.name);
+    let mut result = String::new();
+    if settings.shadow_mode {
+        result.push_str(" (shadow mode, not changed)");
+    } else if let Err(e) = gid
+        .edit_member(&context.http, user.id, |m| m.nickname(&new))
+        .await
+    {
+        error!("Error changing nickname of '{}': {:?}", user.id, e);
+        result.push_str(&format!(" (failed: {e})"));
+    }
+
+    logging::log(
+        context,
+        gid,
+        LogCategory::Automod,
+        "Name screening",
+        format!(
+            "Changed the name of {} ({}).\n{reason}\nBefore: {current}\nAfter: {new}{result}",
+            user.tag(),
+            user.id
+        ),
+        Colour::ORANGE,
+    )
+    .await;
+}
+
+/// Whether a character sorts names before letters. Only the ASCII symbols
+/// and digits below `A` do, so emoji and other scripts are left alone.
+fn is_hoisting(c: char) -> bool { ('!'..='@').contains(&c) }
+
+/// Whether a name starts with a character that sorts it to the top of the
+/// member list
+fn is_hoisted(name: &str) -> bool { matches!(name.chars().next(), Some(c) if is_hoisting(c)) }
+
+/// The guild's safe nickname for a member
+fn safe_nickname(names: &NameSettings, user: &User) -> String
+{
+    let nick = names
+        .safe_nickname
+        .replace("{id}", &user.id.to_string())
+        .replace("{discriminator}", &format!("{:04}", user.discriminator));
+    nick.chars().take(MAX_NICKNAME).collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_is_hoisted()
+    {
+        assert!(is_hoisted("!admin"));
+        assert!(is_hoisted("\"quoted\""));
+        assert!(is_hoisted(".dot"));
+        assert!(is_hoisted("0cool"));
+        assert!(is_hoisted("@home"));
+        assert!(!is_hoisted("Alice"));
+        assert!(!is_hoisted("bob"));
+        assert!(!is_hoisted("_underscore"));
+        assert!(!is_hoisted(""));
+    }
+
+    #[test]
+    fn test_other_scripts()
+    {
+        assert!(!is_hoisted("😀 smiley"));
+        assert!(!is_hoisted("✨sparkles✨"));
+        assert!(!is_hoisted("Ωmega"));
+        assert!(!is_hoisted("Дмитрий"));
+        assert!(!is_hoisted("山田"));
+        assert!(!is_hoisted("محمد"));
+        assert_eq!("!!😀 smiley".trim_start_matches(is_hoisting), "😀 smiley");
+    }
+}
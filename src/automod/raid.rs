@@ -0,0 +1,319 @@
+//! Join-rate raid detection
+//!
+//! Joins are tracked per guild in a sliding window. Past the guild's
+//! threshold the guild enters raid mode: members who join are timed out or
+//! kicked until the raid ends. Everyone who joined during a raid is saved to
+//! `resources.raids` so they can be banned afterwards.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, create_dir_all, read_to_string},
+    io::{Error, ErrorKind},
+    path::PathBuf,
+    sync::Mutex,
+    time::Instant,
+};
+
+use lazy_static::lazy_static;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::{
+        prelude::{GuildId, Member, UserId},
+        timestamp::Timestamp,
+    },
+    prelude::Context,
+    utils::Colour,
+};
+
+use crate::{
+    builtins::{
+        moderation::{self, Moderator},
+        settings,
+    },
+    config::{LogCategory, RaidSettings},
+    logging, CONFIG,
+};
+
+/// The most joins remembered per guild
+const MAX_WINDOW: usize = 1000;
+
+lazy_static! {
+    static ref JOINS: Mutex<HashMap<GuildId, VecDeque<(Instant, UserId)>>> =
+        Mutex::new(HashMap::new());
+    static ref RAIDS: Mutex<Raids> = Mutex::new({
+        let path = CONFIG.resources.raids.clone();
+        if path.exists() {
+            Raids::load(path).unwrap_or_else(|e| {
+                error!("Couldn't load raids: {e}");
+                Raids::default()
+            })
+        } else {
+            Raids::default()
+        }
+    });
+}
+
+/// The latest raid in each guild
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Raids
+{
+    pub raids: Vec<Raid>,
+}
+
+/// A raid and the members who joined during it
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Raid
+{
+    pub gid: GuildId,
+    pub started: Timestamp,
+
+    /// When the raid ended, or `None` if it's ongoing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended: Option<Timestamp>,
+
+    pub members: Vec<UserId>,
+}
+
+impl Raids
+{
+    pub fn load(path: PathBuf) -> Result<Self, Error>
+    {
+        let contents = read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| Error::new(ErrorKind::Other, e))
+    }
+
+    pub fn save(&self, path: PathBuf) -> Result<(), Error>
+    {
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                create_dir_all(parent)?;
+            }
+        }
+
+        let serialized = toml::to_string(&self).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        fs::write(path, serialized)
+    }
+
+    /// A guild's latest raid
+    pub fn get(&self, gid: &GuildId) -> Option<&Raid> { self.raids.iter().find(|r| r.gid == *gid) }
+
+    /// A guild's ongoing raid
+    fn active(&mut self, gid: &GuildId) -> Option<&mut Raid>
+    {
+        self.raids
+            .iter_mut()
+            .find(|r| r.gid == *gid && r.ended.is_none())
+    }
+
+    /// Start a raid, replacing the guild's previous one
+    fn start(&mut self, gid: GuildId, members: Vec<UserId>) -> Timestamp
+    {
+        let started = Timestamp::now();
+        self.raids.retain(|r| r.gid != gid);
+        self.raids.push(Raid {
+            gid,
+            started,
+            ended: None,
+            members,
+        });
+        started
+    }
+}
+
+/// Save the raids, logging any error
+fn save(raids: &Raids)
+{
+    if let Err(e) = raids.save(CONFIG.resources.raids.clone()) {
+        error!("Couldn't save raids: {e}");
+    }
+}
+
+/// Record a member joining, starting raid mode if too many members joined
+/// recently. Returns whether the member was acted on.
+pub async fn member_joined(context: &Context, member: &Member) -> bool
+{
+    let gid = member.guild_id;
+    let settings = settings::get(&gid).raid;
+    if !settings.enabled {
+        return false;
+    }
+
+    let (started, caught) = {
+        let now = Instant::now();
+        let mut joins = JOINS.lock().unwrap();
+        let window = joins.entry(gid).or_default();
+        while let Some((joined, _)) = window.front() {
+            let expired = now.duration_since(*joined).as_secs() > settings.interval;
+            if expired || window.len() >= MAX_WINDOW {
+                window.pop_front();
+            } else {
+                break;
+            }
+        }
+        window.push_back((now, member.user.id));
+
+        let mut raids = RAIDS.lock().unwrap();
+        let ret = if let Some(raid) = raids.active(&gid) {
+            raid.members.push(member.user.id);
+            (None, vec![member.user.id])
+        } else if window.len() > settings.max_joins {
+            // Everyone in the window is part of the raid
+            let members: Vec<UserId> = window.drain(..).map(|(_, uid)| uid).collect();
+            (Some(raids.start(gid, members.clone())), members)
+        } else {
+            return false;
+        };
+        save(&raids);
+        ret
+    };
+
+    if let Some(started) = started {
+        info!("Raid started in '{gid}'");
+        logging::log(
+            context,
+            &gid,
+            LogCategory::Automod,
+            "Raid detected",
+            format!(
+                "{} members joined within {}s. New members will be {} until the raid ends.\nEnd \
+                 it with `/moderation raid end`, and ban everyone who joined with `/moderation \
+                 raid ban`.",
+                caught.len(),
+                settings.interval,
+                describe_action(&settings),
+            ),
+            Colour::RED,
+        )
+        .await;
+
+        if settings.lockdown.duration().num_seconds() > 0 {
+            let context = context.clone();
+            let wait = settings.lockdown.duration().to_std().unwrap_or_default();
+            tokio::spawn(async move {
+                tokio::time::sleep(wait).await;
+                let ended = RAIDS
+                    .lock()
+                    .unwrap()
+                    .get(&gid)
+                    .map_or(false, |r| r.started == started && r.ended.is_none());
+                if ended {
+                    end(&context, &gid).await;
+                }
+            });
+        }
+    }
+
+    for uid in caught {
+        act(context, &gid, uid, &settings).await;
+    }
+    true
+}
+
+/// Timeout or kick a member who joined during a raid
+async fn act(context: &Context, gid: &GuildId, uid: UserId, settings: &RaidSettings)
+{
+    let user = match uid.to_user(&context.http).await {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Error fetching user '{uid}': {:?}", e);
+            return;
+        }
+    };
+
+    let reason = "Joined during a raid".to_string();
+    if settings.kick {
+        moderation::kick::run(context, gid, &user, reason, &Moderator::Automod).await;
+    } else if let Some(e) = moderation::timeout::timeout(
+        context,
+        gid,
+        user,
+        settings.timeout,
+        &Moderator::Automod,
+        Some(&reason),
+    )
+    .await
+    {
+        error!("Error timing out raider '{uid}': {e}");
+    }
+}
+
+/// What happens to members who join during a raid
+fn describe_action(settings: &RaidSettings) -> String
+{
+    if settings.kick {
+        "kicked".to_string()
+    } else {
+        format!("timed out for {}", settings.timeout)
+    }
+}
+
+/// End a guild's raid. Returns a message describing the result.
+pub async fn end(context: &Context, gid: &GuildId) -> String
+{
+    let members = {
+        let mut raids = RAIDS.lock().unwrap();
+        let members = match raids.active(gid) {
+            Some(raid) => {
+                raid.ended = Some(Timestamp::now());
+                raid.members.len()
+            }
+            None => return "There is no raid to end".to_string(),
+        };
+        save(&raids);
+        members
+    };
+    JOINS.lock().unwrap().remove(gid);
+
+    let s = format!("Ended the raid. {members} members joined during it.");
+    info!("Raid ended in '{gid}'");
+    logging::log(
+        context,
+        gid,
+        LogCategory::Moderation,
+        "Raid ended",
+        s.clone(),
+        Colour::DARK_GREEN,
+    )
+    .await;
+    s
+}
+
+/// Ban every member who joined during a guild's latest raid, ending it if it's
+/// ongoing
+pub async fn ban(context: &Context, gid: &GuildId, reason: String) -> String
+{
+    let raid = match RAIDS.lock().unwrap().get(gid) {
+        Some(raid) => raid.clone(),
+        None => return "There is no raid to ban".to_string(),
+    };
+    if raid.ended.is_none() {
+        end(context, gid).await;
+    }
+
+    // Raiders may have left or been kicked, so ban by id
+    let mut banned = 0;
+    for uid in &raid.members {
+        match gid.ban_with_reason(&context.http, *uid, 1, &reason).await {
+            Ok(_) => banned += 1,
+            Err(e) => error!("Error banning raider '{uid}': {:?}", e),
+        }
+    }
+
+    let s = format!(
+        "Banned {banned} of the {} members who joined during the raid",
+        raid.members.len()
+    );
+    info!("{s}");
+    logging::log(
+        context,
+        gid,
+        LogCategory::Moderation,
+        "Raid banned",
+        format!("{s}.\nReason: {reason}"),
+        Colour::RED,
+    )
+    .await;
+    s
+}
@@ -0,0 +1,246 @@
+//! Message flood detection
+//!
+//! Recent messages are tracked per guild member in a sliding window. The
+//! number of tracked members is capped so the tracker can't grow without
+//! bound.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use serenity::model::prelude::{Attachment, GuildId, Message, UserId};
+
+use crate::{
+    builtins::moderation::timeout::{TimeoutTime, MAX_TIMEOUT_SECONDS},
+    config::SpamSettings,
+};
+
+/// The most members tracked at once, across every guild
+const MAX_TRACKED: usize = 10_000;
+
+/// How long a member's offences are remembered after their last one
+const STRIKE_MEMORY: Duration = Duration::from_secs(10 * 60);
+
+/// The most messages remembered per member
+const MAX_WINDOW: usize = 100;
+
+lazy_static! {
+    static ref TRACKER: Mutex<HashMap<(GuildId, UserId), Activity>> = Mutex::new(HashMap::new());
+}
+
+/// A member's recent messages
+struct Activity
+{
+    /// When each message was sent, a hash of its content and the
+    /// fingerprints of its attachments
+    messages: VecDeque<(Instant, Option<u64>, Vec<u64>)>,
+
+    /// The number of floods in a row
+    strikes: u32,
+
+    /// When the member last flooded
+    last_strike: Option<Instant>,
+
+    /// When the member last sent a message
+    last_seen: Instant,
+}
+
+/// A detected flood
+pub struct Flood
+{
+    /// What was exceeded
+    pub reason: String,
+
+    /// How many floods in a row the member has made, including this one
+    pub strikes: u32,
+}
+
+impl Flood
+{
+    /// The timeout for this flood: the guild's timeout, doubled for each
+    /// repeat offence after the warning. `None` for a first offence.
+    pub fn timeout(&self, base: TimeoutTime) -> Option<TimeoutTime>
+    {
+        if self.strikes < 2 {
+            return None;
+        }
+
+        let factor = 1_i64 << (self.strikes - 2).min(16);
+        let seconds = base
+            .duration()
+            .num_seconds()
+            .saturating_mul(factor)
+            .clamp(1, MAX_TIMEOUT_SECONDS);
+        Some(TimeoutTime {
+            seconds: Some(seconds),
+            ..Default::default()
+        })
+    }
+}
+
+/// Record a message and check whether its author is flooding
+pub fn check(gid: &GuildId, msg: &Message, settings: &SpamSettings) -> Option<Flood>
+{
+    let now = Instant::now();
+    let window = Duration::from_secs(settings.interval.max(1));
+
+    let content = msg.content.trim().to_lowercase();
+    let hash = if content.is_empty() {
+        None
+    } else {
+        Some(hash_of(&content))
+    };
+    let fingerprints: Vec<u64> = msg.attachments.iter().map(fingerprint).collect();
+
+    let mut tracker = TRACKER.lock().unwrap();
+    if tracker.len() >= MAX_TRACKED {
+        evict(&mut tracker, now);
+    }
+
+    let activity = tracker.entry((*gid, msg.author.id)).or_insert(Activity {
+        messages: VecDeque::new(),
+        strikes: 0,
+        last_strike: None,
+        last_seen: now,
+    });
+    activity.last_seen = now;
+
+    // Forget old offences
+    if let Some(last) = activity.last_strike {
+        if now.duration_since(last) > STRIKE_MEMORY {
+            activity.strikes = 0;
+            activity.last_strike = None;
+        }
+    }
+
+    // Slide the window
+    while let Some((sent, ..)) = activity.messages.front() {
+        if now.duration_since(*sent) > window {
+            activity.messages.pop_front();
+        } else {
+            break;
+        }
+    }
+    if activity.messages.len() >= MAX_WINDOW {
+        activity.messages.pop_front();
+    }
+    activity
+        .messages
+        .push_back((now, hash, fingerprints.clone()));
+
+    let messages = activity.messages.len();
+    let duplicates = activity
+        .messages
+        .iter()
+        .filter(|(_, h, _)| hash.is_some() && *h == hash)
+        .count();
+    let attachments: usize = activity.messages.iter().map(|(.., a)| a.len()).sum();
+
+    // The most times one of this message's attachments was sent in the window
+    let all_fingerprints = activity.messages.iter().flat_map(|(.., a)| a);
+    let repeated_attachments = fingerprints
+        .iter()
+        .map(|f| all_fingerprints.clone().filter(|x| *x == f).count())
+        .max()
+        .unwrap_or(0);
+
+    let reason = if settings.max_messages > 0 && messages > settings.max_messages {
+        format!("{messages} messages in {}s", window.as_secs())
+    } else if settings.max_duplicates > 0 && duplicates > settings.max_duplicates {
+        format!(
+            "The same message {duplicates} times in {}s",
+            window.as_secs()
+        )
+    } else if settings.max_duplicates > 0 && repeated_attachments > settings.max_duplicates {
+        format!(
+            "The same attachment {repeated_attachments} times in {}s",
+            window.as_secs()
+        )
+    } else if settings.max_attachments > 0 && attachments > settings.max_attachments {
+        format!("{attachments} attachments in {}s", window.as_secs())
+    } else {
+        return None;
+    };
+
+    // Start a new window so the same messages aren't counted twice
+    activity.messages.clear();
+    activity.strikes += 1;
+    activity.last_strike = Some(now);
+
+    Some(Flood {
+        reason,
+        strikes: activity.strikes,
+    })
+}
+
+/// A hash of a value, to compare without keeping it
+fn hash_of(value: impl Hash) -> u64
+{
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A fingerprint of an attachment, from its name and size. Discord doesn't
+/// send a hash of the file itself.
+fn fingerprint(attachment: &Attachment) -> u64
+{
+    hash_of((attachment.filename.to_lowercase(), attachment.size))
+}
+
+/// Drop idle members, then the least recently seen members, until there's
+/// room to track more
+fn evict(tracker: &mut HashMap<(GuildId, UserId), Activity>, now: Instant)
+{
+    tracker.retain(|_, a| now.duration_since(a.last_seen) < STRIKE_MEMORY);
+    if tracker.len() < MAX_TRACKED {
+        return;
+    }
+
+    let mut seen: Vec<Instant> = tracker.values().map(|a| a.last_seen).collect();
+    seen.sort_unstable();
+    let cutoff = seen[seen.len() - MAX_TRACKED * 9 / 10];
+    tracker.retain(|_, a| a.last_seen >= cutoff);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn flood(strikes: u32) -> Flood
+    {
+        Flood {
+            reason: String::new(),
+            strikes,
+        }
+    }
+
+    fn seconds(time: Option<TimeoutTime>) -> i64 { time.unwrap().duration().num_seconds() }
+
+    #[test]
+    fn test_timeout_escalation()
+    {
+        let base = TimeoutTime {
+            minutes: Some(10),
+            ..Default::default()
+        };
+        assert!(flood(1).timeout(base).is_none());
+        assert_eq!(seconds(flood(2).timeout(base)), 600);
+        assert_eq!(seconds(flood(3).timeout(base)), 1_200);
+        assert_eq!(seconds(flood(4).timeout(base)), 2_400);
+        assert_eq!(seconds(flood(30).timeout(base)), MAX_TIMEOUT_SECONDS);
+        assert_eq!(seconds(flood(2).timeout(TimeoutTime::default())), 1);
+
+        // A long base timeout is capped rather than overflowing
+        let huge = TimeoutTime {
+            days: Some(i64::MAX),
+            ..Default::default()
+        };
+        assert_eq!(seconds(flood(18).timeout(huge)), MAX_TIMEOUT_SECONDS);
+    }
+}
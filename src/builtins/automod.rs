@@ -0,0 +1,26 @@
+//! Commands for trying out automod rules
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::{prelude::command::CommandOptionType, Permissions},
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand
+{
+    command
+        .name("automod")
+        .description("Try out this guild's automod rules")
+        .dm_permission(false)
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .create_option(|option| {
+            option
+                .name("test")
+                .kind(CommandOptionType::SubCommand)
+                .description("Check some text against the automod rules without acting on it")
+                .create_sub_option(|opt| {
+                    opt.name("text")
+                        .description("The text to check")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+        })
+}
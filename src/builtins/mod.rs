@@ -1,4 +1,6 @@
+pub mod automod;
 pub mod meta;
 pub mod moderation;
+pub mod owner;
 pub mod random;
 pub mod settings;
@@ -4,14 +4,25 @@ use serenity::{
     prelude::Context,
 };
 
-use super::member_from_id;
-/// Ban a user from a guild
-pub async fn run(context: &Context, gid: &GuildId, user: &User, reason: String, dmd: u8) -> String
+use super::{log_action, Action, Moderator};
+/// Ban a user from a guild, even if they already left
+pub async fn run(
+    context: &Context,
+    gid: &GuildId,
+    user: &User,
+    reason: String,
+    dmd: u8,
+    moderator: &Moderator,
+) -> String
 {
-    let member = member_from_id(context, *gid, user.id).await;
-
-    let s = match member.ban_with_reason(&context.http, dmd, reason).await {
-        Ok(_) => format!("Banned '{}'", user.name),
+    let s = match gid
+        .ban_with_reason(&context.http, user.id, dmd, &reason)
+        .await
+    {
+        Ok(_) => {
+            log_action(context, gid, Action::Ban, moderator, user, Some(&reason)).await;
+            format!("Banned '{}'", user.name)
+        }
         Err(x) => {
             error!("Error banning guild member: {:?}", x);
             format!("Error banning guild member: {x}")
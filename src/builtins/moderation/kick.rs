@@ -4,15 +4,22 @@ use serenity::{
     prelude::Context,
 };
 
-use super::member_from_id;
+use super::{log_action, Action, Moderator};
 
-/// Kick a user from a guild
-pub async fn run(context: &Context, gid: &GuildId, user: &User, reason: String) -> String
+/// Kick a user from a guild. Fails without panicking if they already left.
+pub async fn run(
+    context: &Context,
+    gid: &GuildId,
+    user: &User,
+    reason: String,
+    moderator: &Moderator,
+) -> String
 {
-    let member = member_from_id(context, *gid, user.id).await;
-
-    let s = match member.kick_with_reason(&context.http, &reason).await {
-        Ok(_) => format!("Kicked '{}'", user.name),
+    let s = match gid.kick_with_reason(&context.http, user.id, &reason).await {
+        Ok(_) => {
+            log_action(context, gid, Action::Kick, moderator, user, Some(&reason)).await;
+            format!("Kicked '{}'", user.name)
+        }
         Err(x) => {
             error!("Error kicking guild member: {:?}", x);
             format!("Error kicking guild member: {x:?}")
@@ -1,18 +1,140 @@
 //! Moderation functions for guilds to moderate members. Not available in DM's
 
+use std::fmt;
+
 use serenity::{
     builder::CreateApplicationCommand,
     model::{
-        prelude::{command::CommandOptionType, GuildId, Member, UserId},
+        prelude::{command::CommandOptionType, GuildId, Member, User, UserId},
         Permissions,
     },
     prelude::Context,
+    utils::Colour,
 };
 
-/// Get a member from a user id
-pub async fn member_from_id(context: &Context, gid: GuildId, id: UserId) -> Member
+use crate::{config::LogCategory, logging, warnings::MAX_POINTS};
+
+/// Get a member from a user id. Fails if they're no longer in the guild.
+pub async fn member_from_id(context: &Context, gid: GuildId, id: UserId)
+    -> serenity::Result<Member>
+{
+    gid.member(&context.http, id).await
+}
+
+/// Who took a moderation action
+#[derive(Debug, Clone)]
+pub enum Moderator
+{
+    /// A member, with a command
+    Member(User),
+
+    /// Automod, when a rule matched
+    Automod,
+
+    /// The guild's escalation ladder, when a member's warning points reached a
+    /// step
+    Escalation,
+}
+
+impl fmt::Display for Moderator
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            Self::Member(user) => write!(f, "{} (<@{}>)", user.tag(), user.id),
+            Self::Automod => write!(f, "Automod"),
+            Self::Escalation => write!(f, "Escalation ladder"),
+        }
+    }
+}
+
+/// A moderation action, as posted to the log channel
+#[derive(Debug, Clone, Copy)]
+pub enum Action
 {
-    gid.member(&context.http, id).await.unwrap()
+    Ban,
+    Kick,
+    Warn(u32),
+    Timeout(timeout::TimeoutTime),
+    Release,
+    RemoveWarnings,
+    RemoveWarning(u64),
+    EditWarning(u64),
+}
+
+impl Action
+{
+    fn title(&self) -> &'static str
+    {
+        match self {
+            Self::Ban => "Member banned",
+            Self::Kick => "Member kicked",
+            Self::Warn(_) => "Member warned",
+            Self::Timeout(_) => "Member timed out",
+            Self::Release => "Member released from timeout",
+            Self::RemoveWarnings => "Warnings removed",
+            Self::RemoveWarning(_) => "Warning removed",
+            Self::EditWarning(_) => "Warning edited",
+        }
+    }
+
+    fn colour(&self) -> Colour
+    {
+        match self {
+            Self::Ban => Colour::RED,
+            Self::Kick => Colour::ORANGE,
+            Self::Warn(_) => Colour::GOLD,
+            Self::Timeout(_) => Colour::DARK_ORANGE,
+            Self::Release | Self::RemoveWarnings | Self::RemoveWarning(_) => Colour::DARK_GREEN,
+            Self::EditWarning(_) => Colour::BLUE,
+        }
+    }
+}
+
+/// Post a moderation action to the guild's log channel
+pub async fn log_action(
+    context: &Context,
+    gid: &GuildId,
+    action: Action,
+    moderator: &Moderator,
+    target: &User,
+    reason: Option<&str>,
+)
+{
+    let mut description = format!(
+        "**Member:** {} (<@{}>)\n**Moderator:** {moderator}",
+        target.tag(),
+        target.id
+    );
+    if let Some(reason) = reason {
+        description.push_str(&format!("\n**Reason:** {reason}"));
+    }
+    match action {
+        Action::Timeout(duration) => description.push_str(&format!("\n**Duration:** {duration}")),
+        Action::Warn(points) => description.push_str(&format!("\n**Points:** {points}")),
+        Action::RemoveWarning(id) | Action::EditWarning(id) => {
+            description.push_str(&format!("\n**Warning:** #{id}"))
+        }
+        _ => (),
+    }
+    description.push_str(&format!(
+        "\n**Automatic escalation:** {}",
+        if matches!(moderator, Moderator::Escalation) {
+            "yes"
+        } else {
+            "no"
+        }
+    ));
+
+    logging::log(
+        context,
+        gid,
+        LogCategory::Moderation,
+        action.title(),
+        description,
+        action.colour(),
+    )
+    .await;
 }
 
 pub mod ban;
@@ -71,6 +193,38 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                         .required(false)
                 })
         })
+        .create_option(|option| {
+            option
+                .name("warn")
+                .kind(CommandOptionType::SubCommand)
+                .description("Warn a member")
+                .create_sub_option(|opt| {
+                    opt.name("user")
+                        .description("The user to warn")
+                        .kind(CommandOptionType::User)
+                        .required(true)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("reason")
+                        .description("The reason why you're warning this user")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("points")
+                        .description("How much the warning counts (default: 1)")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .max_int_value(MAX_POINTS)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("preset")
+                        .description("A preset from /settings warnings presets, for its points")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
         .create_option(|option| {
             option
                 .name("get_warnings")
@@ -83,6 +237,13 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                         .kind(CommandOptionType::User)
                         .required(true)
                 })
+                .create_sub_option(|option| {
+                    option
+                        .name("show_removed")
+                        .description("Whether to list removed warnings too (default: false)")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
         })
         .create_option(|option| {
             option
@@ -96,6 +257,55 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                         .kind(CommandOptionType::User)
                         .required(true)
                 })
+                .create_sub_option(|option| {
+                    option
+                        .name("reason")
+                        .description("Why the warnings are being removed")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("remove_warning")
+                .kind(CommandOptionType::SubCommand)
+                .description("Remove a single warning")
+                .create_sub_option(|option| {
+                    option
+                        .name("id")
+                        .description("The warning's ID, as shown by get_warnings")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .required(true)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("reason")
+                        .description("Why the warning is being removed")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("edit_warning")
+                .kind(CommandOptionType::SubCommand)
+                .description("Change the reason of a warning")
+                .create_sub_option(|option| {
+                    option
+                        .name("id")
+                        .description("The warning's ID, as shown by get_warnings")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .required(true)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("reason")
+                        .description("The warning's new reason")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
         })
         .create_option(|option| {
             option
@@ -137,6 +347,38 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                         .kind(CommandOptionType::Integer)
                         .required(false)
                 })
+                .create_sub_option(|option| {
+                    option
+                        .name("reason")
+                        .description("The reason for the timeout")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("raid")
+                .description("Deal with a raid")
+                .kind(CommandOptionType::SubCommandGroup)
+                .create_sub_option(|option| {
+                    option
+                        .name("end")
+                        .description("End raid mode")
+                        .kind(CommandOptionType::SubCommand)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("ban")
+                        .description("Ban everyone who joined during the latest raid")
+                        .kind(CommandOptionType::SubCommand)
+                        .create_sub_option(|option| {
+                            option
+                                .name("reason")
+                                .description("The reason for the bans")
+                                .kind(CommandOptionType::String)
+                                .required(false)
+                        })
+                })
         })
         .create_option(|option| {
             option
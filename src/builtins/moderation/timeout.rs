@@ -8,8 +8,15 @@ use serenity::{
     },
     prelude::Context,
 };
+use std::{fmt, str::FromStr};
 
-use super::member_from_id;
+use super::{log_action, member_from_id, Action, Moderator};
+
+/// The longest duration accepted, about ten years
+const MAX_SECONDS: i64 = 3_650 * 86_400;
+
+/// The longest timeout Discord allows
+pub const MAX_TIMEOUT_SECONDS: i64 = 28 * 86_400;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Default, Clone, Copy, Deserialize, Serialize)]
 pub struct TimeoutTime
@@ -29,42 +36,161 @@ impl TimeoutTime
             && self.hours.is_none()
             && self.days.is_none()
     }
-}
 
-pub fn generate_ending_time(time: TimeoutTime) -> Timestamp
-{
-    let mut when = Utc::now();
-    if let Some(seconds) = time.seconds {
-        when += Duration::seconds(seconds);
+    /// The total length of time, capped at about ten years
+    pub fn duration(&self) -> Duration
+    {
+        let units = [
+            (self.seconds, 1),
+            (self.minutes, 60),
+            (self.hours, 3_600),
+            (self.days, 86_400),
+        ];
+        let seconds = units.iter().fold(0_i64, |total, (n, unit)| {
+            total.saturating_add(n.unwrap_or(0).saturating_mul(*unit))
+        });
+        Duration::seconds(seconds.clamp(-MAX_SECONDS, MAX_SECONDS))
     }
-    if let Some(minutes) = time.minutes {
-        when += Duration::minutes(minutes);
+
+    /// Check that Discord allows a timeout this long: from a second to 28
+    /// days
+    pub fn check_timeout(&self) -> Result<(), String>
+    {
+        if (1..=MAX_TIMEOUT_SECONDS).contains(&self.duration().num_seconds()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Timeouts must be between 1 second and 28 days, not {self}"
+            ))
+        }
     }
-    if let Some(hours) = time.hours {
-        when += Duration::hours(hours);
+
+    /// Parse the duration of a timeout, checking Discord allows it
+    pub fn parse_timeout(s: &str) -> Result<Self, String>
+    {
+        let time: Self = s.parse()?;
+        time.check_timeout()?;
+        Ok(time)
     }
-    if let Some(days) = time.days {
-        when += Duration::days(days);
+}
+
+impl FromStr for TimeoutTime
+{
+    type Err = String;
+
+    /// Parse a duration such as `1d`, `2h30m` or `45s`
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let mut time = Self::default();
+        let mut total: i64 = 0;
+        let mut number = String::new();
+        for c in s
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+        {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+
+            let n: i64 = match number.parse() {
+                Ok(x) => x,
+                Err(_) => return Err(format!("Invalid duration '{s}'")),
+            };
+            number.clear();
+
+            let (unit, seconds) = match c {
+                'd' => (&mut time.days, 86_400),
+                'h' => (&mut time.hours, 3_600),
+                'm' => (&mut time.minutes, 60),
+                's' => (&mut time.seconds, 1),
+                _ => return Err(format!("Unknown unit '{c}' in duration '{s}'")),
+            };
+            total = match n.checked_mul(seconds).and_then(|x| total.checked_add(x)) {
+                Some(x) if x <= MAX_SECONDS => x,
+                _ => return Err(format!("The duration '{s}' is too long. The most is 3650d")),
+            };
+            *unit = Some(unit.unwrap_or(0) + n);
+        }
+
+        if !number.is_empty() || time.is_none() {
+            return Err(format!(
+                "Invalid duration '{s}'. Use units of d, h, m and s (e.g. 1d, 2h30m)"
+            ));
+        }
+        Ok(time)
     }
+}
+
+impl fmt::Display for TimeoutTime
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let units = [
+            (self.days, "d"),
+            (self.hours, "h"),
+            (self.minutes, "m"),
+            (self.seconds, "s"),
+        ];
+        let s: Vec<String> = units
+            .iter()
+            .filter_map(|(n, unit)| n.map(|n| format!("{n}{unit}")))
+            .collect();
 
-    Timestamp::from_unix_timestamp(when.timestamp()).unwrap()
+        if s.is_empty() {
+            write!(f, "0s")
+        } else {
+            write!(f, "{}", s.join(" "))
+        }
+    }
+}
+
+/// When a timeout starting now ends, if that's a time Discord can represent
+pub fn generate_ending_time(time: TimeoutTime) -> Option<Timestamp>
+{
+    let when = Utc::now().checked_add_signed(time.duration())?;
+    Timestamp::from_unix_timestamp(when.timestamp()).ok()
 }
 
+/// Timeout a member. Automatic timeouts only return a message when they fail.
 pub async fn timeout(
     context: &Context,
     gid: &GuildId,
     user: User,
     time: TimeoutTime,
-    auto: bool,
+    moderator: &Moderator,
+    reason: Option<&str>,
 ) -> Option<String>
 {
-    let mut member = member_from_id(context, *gid, user.id).await;
+    if let Err(e) = time.check_timeout() {
+        return Some(format!("Error: {e}"));
+    }
+    let until = match generate_ending_time(time) {
+        Some(x) => x,
+        None => return Some(format!("Error: Can't timeout for {time}")),
+    };
+
+    let mut member = match member_from_id(context, *gid, user.id).await {
+        Ok(x) => x,
+        Err(e) => return Some(format!("Error fetching guild member: {e}")),
+    };
     match member
-        .disable_communication_until_datetime(&context.http, generate_ending_time(time))
+        .disable_communication_until_datetime(&context.http, until)
         .await
     {
         Ok(_) => {
-            if !auto {
+            log_action(
+                context,
+                gid,
+                Action::Timeout(time),
+                moderator,
+                &user,
+                reason,
+            )
+            .await;
+            if matches!(moderator, Moderator::Member(_)) {
                 Some(format!("Timed out user {}", user.name))
             } else {
                 None
@@ -74,11 +200,16 @@ pub async fn timeout(
     }
 }
 
-pub async fn release(context: &Context, gid: &GuildId, user: User) -> String
+pub async fn release(context: &Context, gid: &GuildId, user: User, moderator: &Moderator)
+    -> String
 {
-    let mut member = member_from_id(context, *gid, user.id).await;
+    let mut member = match member_from_id(context, *gid, user.id).await {
+        Ok(x) => x,
+        Err(e) => return format!("Error fetching guild member: {e}"),
+    };
     let s = match member.enable_communication(&context.http).await {
         Ok(_) => {
+            log_action(context, gid, Action::Release, moderator, &user, None).await;
             format!("Released user {} from their timeout.", user.name)
         }
         Err(x) => format!("Error: {x}"),
@@ -86,3 +217,57 @@ pub async fn release(context: &Context, gid: &GuildId, user: User) -> String
     info!("{s}");
     s
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_parse()
+    {
+        let time: TimeoutTime = "1d 2h30m45s".parse().unwrap();
+        assert_eq!(time.days, Some(1));
+        assert_eq!(time.hours, Some(2));
+        assert_eq!(time.minutes, Some(30));
+        assert_eq!(time.seconds, Some(45));
+        assert_eq!(time.duration().num_seconds(), 95_445);
+        assert_eq!("1H1h".parse::<TimeoutTime>().unwrap().hours, Some(2));
+
+        assert!("".parse::<TimeoutTime>().is_err());
+        assert!("10".parse::<TimeoutTime>().is_err());
+        assert!("1w".parse::<TimeoutTime>().is_err());
+        assert!("d".parse::<TimeoutTime>().is_err());
+        assert!("-1d".parse::<TimeoutTime>().is_err());
+    }
+
+    #[test]
+    fn test_overflow()
+    {
+        assert!("999999999999999d".parse::<TimeoutTime>().is_err());
+        assert!("99999999999999999999s".parse::<TimeoutTime>().is_err());
+        assert!("3651d".parse::<TimeoutTime>().is_err());
+        assert!("3650d 1s".parse::<TimeoutTime>().is_err());
+        assert!("3650d".parse::<TimeoutTime>().is_ok());
+
+        // Durations from files aren't parsed, but still mustn't panic
+        let huge = TimeoutTime {
+            days: Some(i64::MAX),
+            seconds: Some(i64::MAX),
+            ..Default::default()
+        };
+        assert_eq!(huge.duration().num_seconds(), MAX_SECONDS);
+        assert!(huge.check_timeout().is_err());
+        assert!(generate_ending_time(huge).is_some());
+    }
+
+    #[test]
+    fn test_timeout_range()
+    {
+        assert!(TimeoutTime::parse_timeout("1s").is_ok());
+        assert!(TimeoutTime::parse_timeout("28d").is_ok());
+        assert!(TimeoutTime::parse_timeout("0s").is_err());
+        assert!(TimeoutTime::parse_timeout("28d1s").is_err());
+        assert!(TimeoutTime::parse_timeout("soon").is_err());
+    }
+}
@@ -1,9 +1,18 @@
 use std::{path::PathBuf, sync::Mutex};
 
-use crate::{config::WarnBehavior, CONFIG};
-use log::info;
+use crate::{
+    config::EscalationAction,
+    logging,
+    warnings::{Change, Warning, Warnings},
+    CONFIG,
+};
+use log::{error, info};
 use serenity::{
-    model::{prelude::GuildId, user::User},
+    model::{
+        prelude::{GuildId, UserId},
+        timestamp::Timestamp,
+        user::User,
+    },
     prelude::Context,
 };
 
@@ -12,133 +21,323 @@ use serenity::{
 
 lazy_static::lazy_static! {
     static ref WARNINGS_FILE: PathBuf = CONFIG.resources.warnings.clone();
-    static ref WARNINGS:  Mutex<bor_warn::warnings::Warnings> = {
+    static ref WARNINGS:  Mutex<Warnings> = {
         if !WARNINGS_FILE.exists()
         {
-            let warnings = bor_warn::warnings::Warnings::new();
+            let warnings = Warnings::default();
             warnings.save(WARNINGS_FILE.to_path_buf()).unwrap();
             Mutex::new(warnings)
         }
         else
         {
-            Mutex::new(bor_warn::warnings::Warnings::load(&WARNINGS_FILE).unwrap())
+            Mutex::new(Warnings::load(WARNINGS_FILE.to_path_buf()).unwrap())
         }
     };
 }
 
-use crate::builtins::settings::SETTINGS as GUILD_SETTINGS;
+use crate::builtins::settings;
+
+use super::{log_action, Action, Moderator};
+
+/// The longest warnings list sent, within Discord's message length
+const MAX_LIST_LENGTH: usize = 1_900;
+
+/// Save the warnings, logging any error
+fn save(warnings: &Warnings)
+{
+    if let Err(e) = warnings.save(WARNINGS_FILE.to_path_buf()) {
+        error!("Couldn't save warnings: {e}");
+    }
+}
 
-pub async fn warn(context: &Context, gid: &GuildId, user: User, reason: String) -> String
+pub async fn warn(
+    context: &Context,
+    gid: &GuildId,
+    user: User,
+    reason: String,
+    points: u32,
+    moderator: &Moderator,
+) -> String
 {
     let uname = user.name.clone();
-    let warning_behavior = {
-        if let (gs, Some(i)) = GUILD_SETTINGS.lock().unwrap().has_guild(gid) {
-            gs.guilds[i].settings.warning_behavior
-        } else {
-            WarnBehavior::Nothing
-        }
+    let settings = settings::get(gid);
+
+    // Only the points of active warnings count towards the escalation ladder
+    let (id, before, after) = {
+        let mut warnings = WARNINGS.lock().unwrap();
+        let expiry = settings.warning_expiry;
+        let before = warnings.get(gid, &user.id).map_or(0, |w| w.points(expiry));
+        let warning = Warning {
+            id: 0,
+            reason: reason.clone(),
+            points,
+            issued: Some(Timestamp::now()),
+            moderator: Some(moderator.to_string()),
+            removed: None,
+            edits: Vec::new(),
+        };
+        let (id, user_warnings) = warnings.add(gid, &user.id, warning);
+        let after = user_warnings.points(expiry);
+        save(&warnings);
+        (id, before, after)
     };
+    log_action(
+        context,
+        gid,
+        Action::Warn(points),
+        moderator,
+        &user,
+        Some(&reason),
+    )
+    .await;
 
-    let count = WARNINGS
-        .lock()
-        .unwrap()
-        .add_warning(gid, &user, reason.clone())
-        .count_warnings(gid, &user);
-
-    match warning_behavior {
-        WarnBehavior::Ban(cap) => {
-            // Ban the user if we're at the warning limit
-            if count >= cap.into() {
-                super::ban::run(
-                    context,
-                    gid,
-                    &user,
-                    format!("Banned for accumulating {count} warnings."),
-                    0,
-                )
-                .await;
+    let mut s = format!("Warned {uname} for {reason} (warning #{id}, {points} points).");
+
+    // Kicks and bans log their own failures. The member may have left since
+    // they were warned, so nothing here assumes they're still in the guild.
+    if let Some(step) = settings.escalation_step(before, after) {
+        let reason = format!("Reached {} warning points.", step.points);
+        let result = match step.action {
+            EscalationAction::Ban => {
+                super::ban::run(context, gid, &user, reason, 0, &Moderator::Escalation).await
             }
-        }
-        WarnBehavior::Nothing => (),
-        WarnBehavior::Kick(cap) => {
-            // Ban the user if we're at the warning limit
-            if count >= cap.into() {
-                super::kick::run(
+            EscalationAction::Kick => {
+                super::kick::run(context, gid, &user, reason, &Moderator::Escalation).await
+            }
+            EscalationAction::Timeout(duration) => {
+                match super::timeout::timeout(
                     context,
                     gid,
-                    &user,
-                    format!("Banned for accumulating {count} warnings."),
+                    user,
+                    duration,
+                    &Moderator::Escalation,
+                    Some(&reason),
                 )
-                .await;
+                .await
+                {
+                    Some(e) => {
+                        error!(
+                            "Error timing out '{uname}' at {} warning points: {e}",
+                            step.points
+                        );
+                        e
+                    }
+                    None => format!("Timed out '{uname}' for {duration}"),
+                }
             }
+        };
+        s.push_str(&format!(" At {} warning points: {result}", step.points));
+    }
+
+    info!("{s}");
+    s
+}
+
+/// Record who removed a warning and why
+fn removal(moderator: &Moderator, reason: &str) -> Change
+{
+    Change {
+        by: moderator.to_string(),
+        at: Timestamp::now(),
+        reason: reason.to_string(),
+    }
+}
+
+pub async fn remove_warns(
+    context: &Context,
+    gid: &GuildId,
+    user: User,
+    reason: String,
+    moderator: &Moderator,
+) -> String
+{
+    let count = {
+        let mut warnings = WARNINGS.lock().unwrap();
+        if warnings.get(gid, &user.id).is_none() {
+            return "There are no warnings to remove".to_string();
         }
-        WarnBehavior::Timeout {
-            warning_count,
-            duration,
-        } => {
-            // Ban the user if we're at the warning limit
-            if count >= warning_count.into() {
-                super::timeout::timeout(context, gid, user, duration, true).await;
+        let mut count = 0;
+        for warning in &mut warnings.get_mut(gid, &user.id).warnings {
+            if warning.removed.is_none() {
+                warning.removed = Some(removal(moderator, &reason));
+                count += 1;
             }
         }
-    }
-
-    // Save the changes
-    WARNINGS
-        .lock()
-        .unwrap()
-        .save(WARNINGS_FILE.to_path_buf())
-        .unwrap();
+        if count == 0 {
+            return "There are no warnings to remove".to_string();
+        }
+        save(&warnings);
+        count
+    };
+    log_action(
+        context,
+        gid,
+        Action::RemoveWarnings,
+        moderator,
+        &user,
+        Some(&reason),
+    )
+    .await;
+    let s = format!("Removed all {count} warnings for user {}", user.name);
 
-    let s = format!("Warned {uname} for {reason}.");
     info!("{s}");
     s
 }
 
-pub fn remove_warns(gid: &GuildId, user: User) -> String
+/// Remove a single warning by its ID
+pub async fn remove_warning(
+    context: &Context,
+    gid: &GuildId,
+    id: u64,
+    reason: String,
+    moderator: &Moderator,
+) -> String
 {
-    // Search for where the guild we're looking for is
-    let guild_pos = match WARNINGS
-        .lock()
-        .unwrap()
-        .guilds
-        .clone()
-        .iter()
-        .position(|g| g.id.to_string() == gid.to_string())
-    {
-        Some(x) => x,
-        None => return "There are no warnings to remove".to_string(),
+    let uid = {
+        let mut warnings = WARNINGS.lock().unwrap();
+        let uid = match warnings.find_mut(gid, id) {
+            Some((_, warning)) if warning.removed.is_some() => {
+                return format!("Warning #{id} was already removed")
+            }
+            Some((uid, warning)) => {
+                warning.removed = Some(removal(moderator, &reason));
+                uid
+            }
+            None => return format!("There is no warning #{id}"),
+        };
+        save(&warnings);
+        uid
     };
-    // Get our guild
-    let guild_warnings = WARNINGS.lock().unwrap().guilds[guild_pos].clone();
-    // Search for where our user is
-    let user_pos = match guild_warnings.users.iter().position(|u| u.user == user) {
-        Some(x) => x,
-        None => return "There are no warnings to remove".to_string(),
+
+    log_warning_change(
+        context,
+        gid,
+        uid,
+        Action::RemoveWarning(id),
+        moderator,
+        &reason,
+    )
+    .await;
+    let s = format!("Removed warning #{id}");
+    info!("{s}");
+    s
+}
+
+/// Change the reason of a single warning by its ID
+pub async fn edit_warning(
+    context: &Context,
+    gid: &GuildId,
+    id: u64,
+    reason: String,
+    moderator: &Moderator,
+) -> String
+{
+    let (uid, old) = {
+        let mut warnings = WARNINGS.lock().unwrap();
+        let (uid, warning) = match warnings.find_mut(gid, id) {
+            Some(x) => x,
+            None => return format!("There is no warning #{id}"),
+        };
+        // Keep the old reason, so the history isn't lost
+        let old = std::mem::replace(&mut warning.reason, reason.clone());
+        warning.edits.push(Change {
+            by: moderator.to_string(),
+            at: Timestamp::now(),
+            reason: old.clone(),
+        });
+        save(&warnings);
+        (uid, old)
     };
-    // Set the user's warnings to an empty vec
-    WARNINGS.lock().unwrap().guilds[guild_pos].users[user_pos].warnings = Vec::new();
-    // Set the warnings count to zero
-    WARNINGS.lock().unwrap().guilds[guild_pos].users[user_pos].warning_count = 0;
-    // Save the changes
-    WARNINGS
-        .lock()
-        .unwrap()
-        .save(WARNINGS_FILE.to_path_buf())
-        .unwrap();
-    let s = format!("Removed all warnings for user {}", user.name);
 
+    let change = format!("{reason} (was: {old})");
+    log_warning_change(
+        context,
+        gid,
+        uid,
+        Action::EditWarning(id),
+        moderator,
+        &change,
+    )
+    .await;
+    let s = format!("Changed the reason of warning #{id} to {reason}");
     info!("{s}");
     s
 }
 
-pub fn get_warns(gid: &GuildId, user: User) -> String
+/// Log a change to a warning, once the warned user is known
+async fn log_warning_change(
+    context: &Context,
+    gid: &GuildId,
+    uid: UserId,
+    action: Action,
+    moderator: &Moderator,
+    reason: &str,
+)
 {
-    let uname = user.name.clone();
-    let s = if let Some(warnings) = WARNINGS.lock().unwrap().get_warnings(*gid, user) {
-        warnings.to_string()
-    } else {
-        format!("User {uname} has no warnings on record.")
+    match uid.to_user(&context.http).await {
+        Ok(user) => log_action(context, gid, action, moderator, &user, Some(reason)).await,
+        Err(e) => error!("Error fetching user '{uid}': {:?}", e),
+    }
+}
+
+/// List a member's warnings, newest first. Removed warnings are only listed
+/// when asked for.
+pub fn get_warns(gid: &GuildId, user: User, show_removed: bool) -> String
+{
+    let expiry = settings::get(gid).warning_expiry;
+    let warnings = WARNINGS.lock().unwrap();
+    let s = match warnings.get(gid, &user.id) {
+        Some(w) if !w.warnings.is_empty() => {
+            let mut s = format!(
+                "{} has {} active warnings, worth {} points:",
+                user.name,
+                w.active(expiry).count(),
+                w.points(expiry)
+            );
+            let shown: Vec<_> = w
+                .warnings
+                .iter()
+                .rev()
+                .filter(|warning| show_removed || warning.removed.is_none())
+                .collect();
+            for (i, warning) in shown.iter().enumerate() {
+                let status = match &warning.removed {
+                    Some(r) => {
+                        format!(
+                            " (removed by {}: {})",
+                            r.by,
+                            logging::shorten(&r.reason, 100)
+                        )
+                    }
+                    None if warning.is_past(expiry) => " (expired)".to_string(),
+                    None => String::new(),
+                };
+                let issued = match warning.issued {
+                    Some(t) => format!("<t:{}:d>", t.unix_timestamp()),
+                    None => "(date unknown)".to_string(),
+                };
+                let line = format!(
+                    "\n#{} {issued} {} [{} points]{status}",
+                    warning.id,
+                    logging::shorten(&warning.reason, 200),
+                    warning.points,
+                );
+
+                // Keep the list within Discord's message length
+                if s.len() + line.len() > MAX_LIST_LENGTH {
+                    s.push_str(&format!("\n...and {} older warnings", shown.len() - i));
+                    break;
+                }
+                s.push_str(&line);
+            }
+
+            let removed = w.warnings.len() - shown.len();
+            if removed > 0 {
+                s.push_str(&format!("\n({removed} removed warnings hidden)"));
+            }
+            s
+        }
+        _ => format!("User {} has no warnings on record.", user.name),
     };
 
     info!("{s}");
@@ -0,0 +1,53 @@
+//! Commands only the bot's owner may use
+
+use log::error;
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::{prelude::UserId, Permissions},
+    prelude::Context,
+};
+
+use crate::reload;
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand
+{
+    command
+        .name("reload")
+        .description("Reload the restricted words and config (Bot owner only)")
+        .dm_permission(true)
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+}
+
+/// Whether a user owns the bot, or is on the team that does
+async fn is_owner(context: &Context, uid: &UserId) -> bool
+{
+    match context.http.get_current_application_info().await {
+        Ok(info) => {
+            info.owner.id == *uid
+                || info
+                    .team
+                    .map_or(false, |t| t.members.iter().any(|m| m.user.id == *uid))
+        }
+        Err(e) => {
+            error!("Error fetching application info: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Reload the restricted words and config, keeping the current ones if the new
+/// ones fail to load
+pub async fn reload(context: &Context, uid: &UserId) -> String
+{
+    if !is_owner(context, uid).await {
+        return "Only the bot's owner can reload it".to_string();
+    }
+
+    match reload::reload() {
+        Ok(summary) => summary,
+        Err(e) => {
+            error!("{e}");
+            format!("Error: {e}. Keeping the current configuration.")
+        }
+    }
+}
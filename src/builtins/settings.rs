@@ -1,17 +1,33 @@
 use std::path::PathBuf;
 use std::sync::Mutex;
 
-use crate::config::{self, GuildSettings, WarnBehavior};
-use crate::CONFIG;
+use crate::builtins::moderation::timeout::TimeoutTime;
+use crate::config::{
+    self, AutomodAction, AutomodRule, EscalationAction, EscalationStep, GuildSettings, LogCategory,
+    LogChannels, MatchMode, RestrictedWord, RestrictedWords, WarnBehavior, WarningPreset,
+};
+use crate::warnings::{parse_points, MAX_POINTS};
+use crate::{automod, filtering, CONFIG};
 use lazy_static::lazy_static;
+use log::error;
 use serenity::{
-    builder::CreateApplicationCommand,
+    builder::{CreateApplicationCommand, CreateApplicationCommandOption},
     model::{
-        prelude::{command::CommandOptionType, GuildId, PartialChannel},
+        prelude::{
+            command::CommandOptionType,
+            interaction::application_command::{CommandDataOption, CommandDataOptionValue},
+            Attachment, ChannelId, GuildId, PartialChannel, RoleId,
+        },
         Permissions,
     },
 };
 
+/// The number of restricted words shown per page by `settings filter list`
+const FILTER_PAGE_SIZE: usize = 15;
+
+/// The largest word list that `settings filter import` accepts, in bytes
+const FILTER_IMPORT_LIMIT: u64 = 1024 * 1024;
+
 lazy_static! {
     static ref SETTINGS_PATH: PathBuf = CONFIG.resources.guild_settings.clone();
     pub(crate) static ref SETTINGS: Mutex<GuildSettings> = Mutex::new({
@@ -42,32 +58,710 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                         .kind(CommandOptionType::Channel)
                         .required(true)
                 })
+                .create_sub_option(log_category_option)
         })
         .create_option(|option| {
             option
                 .name("remove_log")
                 .kind(CommandOptionType::SubCommand)
                 .description("Stop using the current logging channel")
+                .create_sub_option(log_category_option)
         })
         .create_option(|option| {
             option
                 .name("set_warn_behavior")
                 .kind(CommandOptionType::SubCommand)
-                .description("Set the action when a specified number of warnings is met")
+                .description("Set the action when a specified number of warning points is met")
                 .create_sub_option(|opt| {
                     opt.name("behavior")
-                        .description("Nothing, Ban, Kick, Timeout")
+                        .description("What to do when the limit is met")
                         .kind(CommandOptionType::String)
+                        .add_string_choice("Nothing", "nothing")
+                        .add_string_choice("Ban", "ban")
+                        .add_string_choice("Kick", "kick")
+                        .add_string_choice("Timeout", "timeout")
                         .required(true)
                 })
                 .create_sub_option(|opt| {
                     opt.name("max")
+                        .description("The warning points needed to take action")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .max_int_value(MAX_POINTS)
+                        .required(false)
+                })
+                .create_sub_option(duration_option)
+        })
+        .create_option(|option| {
+            option
+                .name("filter")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Manage this guild's restricted words")
+                .create_sub_option(|opt| {
+                    opt.name("add")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Add a restricted word")
+                        .create_sub_option(|opt| {
+                            opt.name("word")
+                                .description("The word or pattern to restrict")
+                                .kind(CommandOptionType::String)
+                                .required(true)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("mode")
+                                .description("How the word is matched (Default: word)")
+                                .kind(CommandOptionType::String)
+                                .add_string_choice("Whole word", "word")
+                                .add_string_choice("Anywhere in a word", "substring")
+                                .add_string_choice("Wildcards (* and ?)", "glob")
+                                .add_string_choice("Regular expression", "regex")
+                                .required(false)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("whole_word")
+                                .description("Only match whole words")
+                                .kind(CommandOptionType::Boolean)
+                                .required(false)
+                        })
+                        .create_sub_option(|opt| action_option(opt, false))
+                        .create_sub_option(duration_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("action")
+                        .kind(CommandOptionType::SubCommand)
                         .description(
-                            "The number of warnings needed to take action (e.g. 3, 255) 255 Max",
+                            "Set the action for restricted words that don't have their own",
                         )
-                        .kind(CommandOptionType::Integer)
+                        .create_sub_option(|opt| action_option(opt, true))
+                        .create_sub_option(duration_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("remove")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove a restricted word")
+                        .create_sub_option(|opt| {
+                            opt.name("word")
+                                .description("The word or pattern to remove")
+                                .kind(CommandOptionType::String)
+                                .required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("list")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("List the restricted words")
+                        .create_sub_option(|opt| {
+                            opt.name("page")
+                                .description("The page to show (Default: 1)")
+                                .kind(CommandOptionType::Integer)
+                                .min_int_value(1)
+                                .required(false)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("clear")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove every restricted word")
+                })
+                .create_sub_option(|opt| {
+                    opt.name("import")
+                        .kind(CommandOptionType::SubCommand)
+                        .description(
+                            "Add restricted words from a file (one word per line, or TOML)",
+                        )
+                        .create_sub_option(|opt| {
+                            opt.name("file")
+                                .description("The file to import")
+                                .kind(CommandOptionType::Attachment)
+                                .required(true)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("replace")
+                                .description("Replace the current words instead of adding to them")
+                                .kind(CommandOptionType::Boolean)
+                                .required(false)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("export")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Download the restricted words as a file")
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("shadow_mode")
+                .kind(CommandOptionType::SubCommand)
+                .description("Only log automod hits instead of acting on them")
+                .create_sub_option(|opt| {
+                    opt.name("enabled")
+                        .description("Whether shadow mode is on")
+                        .kind(CommandOptionType::Boolean)
+                        .required(true)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("spam")
+                .kind(CommandOptionType::SubCommand)
+                .description("Configure message flood detection")
+                .create_sub_option(|opt| {
+                    opt.name("enabled")
+                        .description("Whether to detect message floods")
+                        .kind(CommandOptionType::Boolean)
                         .required(true)
                 })
+                .create_sub_option(|opt| {
+                    opt.name("interval")
+                        .description("The length of time messages are counted over, in seconds")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .max_int_value(300)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("max_messages")
+                        .description("The most messages allowed in the interval (0 disables)")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(0)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("max_duplicates")
+                        .description(
+                            "The most identical messages or attachments allowed in the interval \
+                             (0 disables)",
+                        )
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(0)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("max_attachments")
+                        .description("The most attachments allowed in the interval (0 disables)")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(0)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("timeout")
+                        .description("Timeout for repeat floods, doubled each time (e.g. 10m, 1h)")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("mentions")
+                .kind(CommandOptionType::SubCommand)
+                .description("Configure mass mention protection")
+                .create_sub_option(|opt| {
+                    opt.name("max_mentions")
+                        .description(
+                            "The most users and roles one message may mention (0 disables)",
+                        )
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(0)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("block_everyone")
+                        .description("Remove @everyone and @here from members without permission")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("timeout")
+                        .description("How long to timeout the author (e.g. 1h, 30m)")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("exempt_role")
+                        .description("Let members with this role mention freely")
+                        .kind(CommandOptionType::Role)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("unexempt_role")
+                        .description("Stop exempting this role")
+                        .kind(CommandOptionType::Role)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("links")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Configure link and invite filtering")
+                .create_sub_option(|opt| {
+                    opt.name("allow")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Always allow links to a domain and its subdomains")
+                        .create_sub_option(domain_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("deny")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove links to a domain and its subdomains")
+                        .create_sub_option(domain_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("remove")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove a domain from the allowed and denied domains")
+                        .create_sub_option(domain_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("set")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Configure invite and phishing filtering")
+                        .create_sub_option(|opt| {
+                            opt.name("block_invites")
+                                .description("Remove invites to other servers")
+                                .kind(CommandOptionType::Boolean)
+                                .required(false)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("block_phishing")
+                                .description("Remove links to known phishing domains")
+                                .kind(CommandOptionType::Boolean)
+                                .required(false)
+                        })
+                        .create_sub_option(|opt| action_option(opt, false))
+                        .create_sub_option(duration_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("show")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Show the link filtering settings")
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("names")
+                .kind(CommandOptionType::SubCommand)
+                .description("Configure nickname and username screening")
+                .create_sub_option(|opt| {
+                    opt.name("filter")
+                        .description("Reset names containing restricted words")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("dehoist")
+                        .description("Strip characters like ! from the start of names")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("safe_nickname")
+                        .description(
+                            "The nickname given in place of a bad name ({id} and {discriminator} \
+                             are filled in)",
+                        )
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("raid")
+                .kind(CommandOptionType::SubCommand)
+                .description("Configure raid detection")
+                .create_sub_option(|opt| {
+                    opt.name("enabled")
+                        .description("Whether raid detection is on")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("max_joins")
+                        .description("The most members that may join within the interval")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("interval")
+                        .description("The length of the interval in seconds")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .max_int_value(3600)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("kick")
+                        .description("Kick new members during a raid instead of timing them out")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("timeout")
+                        .description("How long to timeout members who join during a raid (e.g. 1d)")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("lockdown")
+                        .description("How long raid mode lasts (e.g. 15m, 0s to end it manually)")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("gate")
+                .kind(CommandOptionType::SubCommand)
+                .description("Set the account age and avatar requirements for new members")
+                .create_sub_option(|opt| {
+                    opt.name("min_account_age")
+                        .description("How old accounts must be (e.g. 7d, 0s for no minimum)")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("require_avatar")
+                        .description("Whether accounts must have an avatar")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("quarantine_role")
+                        .description("Give members who fail this role instead of kicking them")
+                        .kind(CommandOptionType::Role)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("kick")
+                        .description("Kick members who fail instead of quarantining them")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("attachments")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Configure attachment filtering")
+                .create_sub_option(|opt| {
+                    opt.name("deny_extension")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove attachments with a file extension")
+                        .create_sub_option(extension_option)
+                        .create_sub_option(|opt| channel_option(opt, "Only in this channel"))
+                })
+                .create_sub_option(|opt| {
+                    opt.name("allow_extension")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Stop removing attachments with a file extension")
+                        .create_sub_option(extension_option)
+                        .create_sub_option(|opt| channel_option(opt, "Only in this channel"))
+                })
+                .create_sub_option(|opt| {
+                    opt.name("deny_type")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove attachments with a content type")
+                        .create_sub_option(content_type_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("allow_type")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Stop removing attachments with a content type")
+                        .create_sub_option(content_type_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("max_size")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Set the largest attachment allowed")
+                        .create_sub_option(|opt| {
+                            opt.name("kilobytes")
+                                .description("The size limit in kilobytes (0 removes the limit)")
+                                .kind(CommandOptionType::Integer)
+                                .min_int_value(0)
+                                .max_int_value(automod::attachments::MAX_SIZE)
+                                .required(true)
+                        })
+                        .create_sub_option(|opt| channel_option(opt, "Only in this channel"))
+                })
+                .create_sub_option(|opt| {
+                    opt.name("action")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Set what happens when an attachment is removed")
+                        .create_sub_option(|opt| action_option(opt, true))
+                        .create_sub_option(duration_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("show")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Show the attachment filtering settings")
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("escalation")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Configure what happens as members' warning points rise")
+                .create_sub_option(|opt| {
+                    opt.name("add")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Add a step, replacing any step with the same points")
+                        .create_sub_option(|opt| {
+                            opt.name("points")
+                                .description("The warning points that take the step")
+                                .kind(CommandOptionType::Integer)
+                                .min_int_value(1)
+                                .max_int_value(MAX_POINTS)
+                                .required(true)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("action")
+                                .description("What to do")
+                                .kind(CommandOptionType::String)
+                                .add_string_choice("Timeout", "timeout")
+                                .add_string_choice("Kick", "kick")
+                                .add_string_choice("Ban", "ban")
+                                .required(true)
+                        })
+                        .create_sub_option(duration_option)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("remove")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove a step")
+                        .create_sub_option(|opt| {
+                            opt.name("points")
+                                .description("The warning points of the step")
+                                .kind(CommandOptionType::Integer)
+                                .min_int_value(1)
+                                .max_int_value(MAX_POINTS)
+                                .required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("list")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("List the escalation steps")
+                })
+                .create_sub_option(|opt| {
+                    opt.name("clear")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove every escalation step")
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("warnings")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Configure warnings")
+                .create_sub_option(|opt| {
+                    opt.name("expiry")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Set how long warnings count towards the escalation ladder")
+                        .create_sub_option(|opt| {
+                            opt.name("duration")
+                                .description("How long warnings last (e.g. 90d), or 0 for forever")
+                                .kind(CommandOptionType::String)
+                                .required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("add_preset")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Name a number of points for warnings (e.g. spam = 1)")
+                        .create_sub_option(|opt| {
+                            opt.name("name")
+                                .description("The preset's name, used as the warning's reason")
+                                .kind(CommandOptionType::String)
+                                .required(true)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("points")
+                                .description("How much warnings with the preset count")
+                                .kind(CommandOptionType::Integer)
+                                .min_int_value(1)
+                                .max_int_value(MAX_POINTS)
+                                .required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("remove_preset")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Remove a warning preset")
+                        .create_sub_option(|opt| {
+                            opt.name("name")
+                                .description("The preset's name")
+                                .kind(CommandOptionType::String)
+                                .required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("presets")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("List the warning presets")
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("member_log")
+                .kind(CommandOptionType::SubCommand)
+                .description("Choose which member events are logged")
+                .create_sub_option(|opt| {
+                    opt.name("joins")
+                        .description("Log members joining, with their account age")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("leaves")
+                        .description("Log members leaving, with when they joined and their roles")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("nicknames")
+                        .description("Log nickname changes")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_sub_option(|opt| {
+                    opt.name("roles")
+                        .description("Log roles being given or taken away")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("message_log")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Configure logging of deleted and edited messages")
+                .create_sub_option(|opt| {
+                    opt.name("set")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Choose which messages are logged")
+                        .create_sub_option(|opt| {
+                            opt.name("deletes")
+                                .description("Log deleted messages")
+                                .kind(CommandOptionType::Boolean)
+                                .required(false)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("edits")
+                                .description("Log edited messages")
+                                .kind(CommandOptionType::Boolean)
+                                .required(false)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("watch")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Only log the watched channels")
+                        .create_sub_option(|opt| {
+                            channel_option(opt, "The channel to watch").required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("ignore")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Never log a channel")
+                        .create_sub_option(|opt| {
+                            channel_option(opt, "The channel to ignore").required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("reset")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Stop watching or ignoring a channel")
+                        .create_sub_option(|opt| {
+                            channel_option(opt, "The channel to reset").required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("show")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Show the message logging settings")
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("exempt")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Channels and roles that bypass automod rules")
+                .create_sub_option(|opt| {
+                    opt.name("add")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Exempt a channel or role from an automod rule")
+                        .create_sub_option(rule_option)
+                        .create_sub_option(|opt| {
+                            opt.name("channel")
+                                .description("The channel to exempt")
+                                .kind(CommandOptionType::Channel)
+                                .required(false)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("role")
+                                .description("The role to exempt")
+                                .kind(CommandOptionType::Role)
+                                .required(false)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("remove")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Stop exempting a channel or role from an automod rule")
+                        .create_sub_option(rule_option)
+                        .create_sub_option(|opt| {
+                            opt.name("channel")
+                                .description("The channel to stop exempting")
+                                .kind(CommandOptionType::Channel)
+                                .required(false)
+                        })
+                        .create_sub_option(|opt| {
+                            opt.name("role")
+                                .description("The role to stop exempting")
+                                .kind(CommandOptionType::Role)
+                                .required(false)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("list")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("List the exempt channels and roles")
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("allowlist")
+                .kind(CommandOptionType::SubCommandGroup)
+                .description("Words that are never treated as restricted words")
+                .create_sub_option(|opt| {
+                    opt.name("add")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Allow a word")
+                        .create_sub_option(|opt| {
+                            opt.name("word")
+                                .description("The word to allow (e.g. classic)")
+                                .kind(CommandOptionType::String)
+                                .required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("remove")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("Stop allowing a word")
+                        .create_sub_option(|opt| {
+                            opt.name("word")
+                                .description("The word to remove")
+                                .kind(CommandOptionType::String)
+                                .required(true)
+                        })
+                })
+                .create_sub_option(|opt| {
+                    opt.name("list")
+                        .kind(CommandOptionType::SubCommand)
+                        .description("List the allowed words")
+                })
         })
         .create_option(|option| {
             option
@@ -83,22 +777,205 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
         })
 }
 
-pub fn set_warning_behavior(gid: &GuildId, w: WarnBehavior) -> String
+/// Get a copy of a guild's settings, or the defaults if it has none
+pub fn get(gid: &GuildId) -> config::Settings
 {
-    let (_, x) = SETTINGS.lock().unwrap().has_guild(gid);
+    match SETTINGS.lock().unwrap().get(gid) {
+        Some(s) => s.clone(),
+        None => config::Settings::new(),
+    }
+}
 
-    if let Some(x) = x {
-        SETTINGS.lock().unwrap().guilds[x]
-            .settings
-            .set_warning_behavior(w);
+/// Modify a guild's settings, creating them if they don't exist, and save the
+/// changes to disk
+pub fn update<T>(gid: &GuildId, f: impl FnOnce(&mut config::Settings) -> T) -> T
+{
+    let mut settings = SETTINGS.lock().unwrap();
+
+    let ret = if let Some(s) = settings.get_mut(gid) {
+        f(s)
     } else {
-        let s = config::Settings::new().set_warning_behavior(w).to_owned();
+        let mut s = config::Settings::new();
+        let ret = f(&mut s);
 
         // Make a guild with the settings
-        SETTINGS.lock().unwrap().add_guild(*gid, s);
+        settings.add_guild(*gid, s);
+        ret
+    };
+
+    if let Err(e) = settings.save(SETTINGS_PATH.to_path_buf()) {
+        error!("Couldn't save guild settings: {e}");
+    }
+    ret
+}
+
+/// An option choosing an automod action
+fn action_option(
+    opt: &mut CreateApplicationCommandOption,
+    required: bool,
+) -> &mut CreateApplicationCommandOption
+{
+    opt.name("action")
+        .description("What to do when the rule matches")
+        .kind(CommandOptionType::String)
+        .add_string_choice("Log only", "log")
+        .add_string_choice("Delete", "delete")
+        .add_string_choice("Delete and warn", "warn")
+        .add_string_choice("Delete and timeout", "timeout")
+        .add_string_choice("Delete and kick", "kick")
+        .required(required)
+}
+
+/// An option for a length of time
+fn duration_option(opt: &mut CreateApplicationCommandOption)
+    -> &mut CreateApplicationCommandOption
+{
+    opt.name("duration")
+        .description("How long to timeout for (e.g. 1d, 2h30m, 45s)")
+        .kind(CommandOptionType::String)
+        .required(false)
+}
+
+/// An option choosing a kind of automod rule
+fn rule_option(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption
+{
+    opt.name("rule")
+        .description("The automod rule")
+        .kind(CommandOptionType::String)
+        .add_string_choice("Every rule", "all")
+        .add_string_choice("Restricted words", "words")
+        .add_string_choice("Links and invites", "links")
+        .add_string_choice("Flood detection", "spam")
+        .add_string_choice("Mass mentions", "mentions")
+        .add_string_choice("Attachments", "attachments")
+        .required(true)
+}
+
+/// An option for a file extension
+fn extension_option(opt: &mut CreateApplicationCommandOption)
+    -> &mut CreateApplicationCommandOption
+{
+    opt.name("extension")
+        .description("The file extension (e.g. exe)")
+        .kind(CommandOptionType::String)
+        .required(true)
+}
+
+/// An option for a content type
+fn content_type_option(
+    opt: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption
+{
+    opt.name("content_type")
+        .description("The content type (e.g. application/x-msdownload, video/*)")
+        .kind(CommandOptionType::String)
+        .required(true)
+}
+
+/// An option for a category of logged events
+fn log_category_option(
+    opt: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption
+{
+    opt.name("category")
+        .description("The events the channel is for (default: all)")
+        .kind(CommandOptionType::String)
+        .add_string_choice("All", "all")
+        .add_string_choice("Moderation actions", "moderation")
+        .add_string_choice("Automod", "automod")
+        .add_string_choice("Deleted and edited messages", "messages")
+        .add_string_choice("Member joins, leaves and changes", "members")
+        .required(false)
+}
+
+/// An optional channel
+fn channel_option<'a>(
+    opt: &'a mut CreateApplicationCommandOption,
+    description: &str,
+) -> &'a mut CreateApplicationCommandOption
+{
+    opt.name("channel")
+        .description(description)
+        .kind(CommandOptionType::Channel)
+        .required(false)
+}
+
+/// An option for a domain name
+fn domain_option(opt: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption
+{
+    opt.name("domain")
+        .description("The domain (e.g. example.com)")
+        .kind(CommandOptionType::String)
+        .required(true)
+}
+
+/// Run the `settings set_warn_behavior` subcommand
+pub fn set_warn_behavior(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let (mut behavior, mut max, mut duration) = (String::new(), None, None);
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("behavior", Some(CommandDataOptionValue::String(b))) => behavior = b,
+            ("max", Some(CommandDataOptionValue::Integer(m))) => {
+                max = match parse_points(m) {
+                    Ok(x) => Some(x),
+                    Err(e) => return format!("Error: {e}"),
+                }
+            }
+            ("duration", Some(CommandDataOptionValue::String(d))) => duration = Some(d),
+            _ => unreachable!(),
+        }
     }
 
-    "Set warning behavior".to_string()
+    let count = match max {
+        Some(m) => m,
+        None if behavior == "nothing" => 0,
+        None => return "Error: Give the number of warning points that take action".to_string(),
+    };
+
+    let w = match &*behavior {
+        "nothing" => WarnBehavior::Nothing,
+        "ban" => WarnBehavior::Ban(count),
+        "kick" => WarnBehavior::Kick(count),
+        "timeout" => {
+            let duration = match duration.map(|d| TimeoutTime::parse_timeout(&d)) {
+                Some(Ok(x)) => x,
+                Some(Err(e)) => return format!("Error: {e}"),
+                None => return "Error: Timeouts need a duration".to_string(),
+            };
+            WarnBehavior::Timeout {
+                warning_count: count,
+                duration,
+            }
+        }
+        x => return format!("Error: Unknown behavior '{x}'"),
+    };
+
+    set_warning_behavior(gid, w)
+}
+
+/// Replace a guild's escalation ladder with a single behavior, and describe
+/// the result. A ladder of several steps is left alone, so it isn't lost by
+/// mistake.
+pub fn set_warning_behavior(gid: &GuildId, w: WarnBehavior) -> String
+{
+    let steps = update(gid, |s| {
+        if s.escalation.len() > 1 {
+            return Err(s.escalation.len());
+        }
+        Ok(s.set_warning_behavior(w).escalation.clone())
+    });
+
+    match steps {
+        Ok(steps) => format!("Set warning behavior.\n{}", describe_escalation(&steps)),
+        Err(n) => {
+            format!(
+                "Error: The escalation ladder has {n} steps, which this would replace. Change \
+                 them with `/settings escalation`, or clear them with `/settings escalation \
+                 clear` first."
+            )
+        }
+    }
 }
 
 pub fn set_wiki_limit(gid: &GuildId, limit: usize) -> String
@@ -125,48 +1002,1160 @@ pub struct Log {}
 
 impl Log
 {
-    pub fn set_log(c: PartialChannel, gid: &GuildId) -> String
+    pub fn set_log(c: PartialChannel, category: LogCategory, gid: &GuildId) -> String
     {
         let cs = c.clone().name.unwrap();
-        let (_, x) = SETTINGS.lock().unwrap().has_guild(gid);
+        let id = c.id;
 
-        if let Some(x) = x {
-            SETTINGS.lock().unwrap().guilds[x]
-                .settings
-                .set_log_channel(c);
-        } else {
-            let s = config::Settings::new().set_log_channel(c).to_owned();
+        update(gid, |s| {
+            match s.log_channels.get_mut(category) {
+                Some(channel) => *channel = Some(id),
+                None => {
+                    s.set_log_channel(c);
+                }
+            }
+        });
 
-            // Make a guild with the settings
-            SETTINGS.lock().unwrap().add_guild(*gid, s);
+        match category {
+            LogCategory::All => format!("Set log channel to {cs}"),
+            _ => format!("Set the {category} log channel to {cs}"),
         }
+    }
 
-        SETTINGS
-            .lock()
-            .unwrap()
-            .save(SETTINGS_PATH.to_path_buf())
-            .unwrap();
-        format!("Set log channel to {cs}")
+    pub fn remove_log(category: LogCategory, gid: &GuildId) -> String
+    {
+        update(gid, |s| {
+            match s.log_channels.get_mut(category) {
+                Some(channel) => {
+                    *channel = None;
+                    match s.log_channel {
+                        Some(ref c) => {
+                            format!(
+                                "Removed the {category} log channel. Those events are logged to \
+                                 <#{}>.",
+                                c.id
+                            )
+                        }
+                        None => format!("Removed the {category} log channel"),
+                    }
+                }
+                None => {
+                    s.log_channel = None;
+                    s.log_channels = LogChannels::default();
+                    "Removed every log channel".to_string()
+                }
+            }
+        })
+    }
+}
+
+/// Run a `settings allowlist` subcommand
+pub fn allowlist(gid: &GuildId, group: CommandDataOption) -> String
+{
+    let mut ret = "Failed".to_string();
+    for sub in group.options {
+        let mut word = String::new();
+        for opt in sub.options {
+            match &*opt.name {
+                "word" => {
+                    if let Some(CommandDataOptionValue::String(w)) = opt.resolved {
+                        word = w.trim().to_lowercase();
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        ret = match &*sub.name {
+            "add" => {
+                if update(gid, |s| s.add_allowed_word(word.clone())) {
+                    format!("Allowed '{word}'")
+                } else {
+                    format!("'{word}' is already allowed")
+                }
+            }
+            "remove" => {
+                if update(gid, |s| s.remove_allowed_word(&word)) {
+                    format!("Removed '{word}' from the allowlist")
+                } else {
+                    format!("'{word}' is not on the allowlist")
+                }
+            }
+            "list" => {
+                let words = get(gid).allowed_words;
+                if words.is_empty() {
+                    "No words are on the allowlist".to_string()
+                } else {
+                    format!("Allowed words: {}", words.join(", "))
+                }
+            }
+            _ => format!("{} Failed!", sub.name),
+        };
+    }
+    ret
+}
+
+/// Run a `settings filter` subcommand. Returns the response, and a file to
+/// attach to it.
+pub async fn filter(gid: &GuildId, group: CommandDataOption)
+    -> (String, Option<(String, Vec<u8>)>)
+{
+    let mut ret = ("Failed".to_string(), None);
+    for sub in group.options {
+        let mut word = String::new();
+        let mut mode = MatchMode::Word;
+        let mut whole_word = false;
+        let mut page = 1;
+        let mut file = None;
+        let mut replace = false;
+        let mut action = None;
+        let mut duration = None;
+        for opt in sub.options {
+            match (&*opt.name, opt.resolved) {
+                ("word", Some(CommandDataOptionValue::String(w))) => word = w.trim().to_string(),
+                ("mode", Some(CommandDataOptionValue::String(m))) => {
+                    mode = m.parse().unwrap_or_default()
+                }
+                ("whole_word", Some(CommandDataOptionValue::Boolean(b))) => whole_word = b,
+                ("page", Some(CommandDataOptionValue::Integer(p))) => page = p.max(1) as usize,
+                ("file", Some(CommandDataOptionValue::Attachment(a))) => file = Some(a),
+                ("replace", Some(CommandDataOptionValue::Boolean(b))) => replace = b,
+                ("action", Some(CommandDataOptionValue::String(a))) => action = Some(a),
+                ("duration", Some(CommandDataOptionValue::String(d))) => duration = Some(d),
+                _ => unreachable!(),
+            }
+        }
+
+        // Parse the action and its duration
+        let duration = match duration.map(|d| d.parse::<TimeoutTime>()).transpose() {
+            Ok(x) => x,
+            Err(e) => return (format!("Error: {e}"), None),
+        };
+        let action = match action
+            .map(|a| AutomodAction::from_name(&a, duration))
+            .transpose()
+        {
+            Ok(x) => x,
+            Err(e) => return (format!("Error: {e}"), None),
+        };
+
+        ret = match &*sub.name {
+            "add" => {
+                let word = RestrictedWord::new(word, mode, whole_word, action);
+                if let Err(e) = filtering::validate(&word) {
+                    (
+                        format!("Invalid restricted word '{}': {e}", word.pattern()),
+                        None,
+                    )
+                } else if update(gid, |s| s.add_restricted_word(word.clone())) {
+                    (format!("Added restricted word '{word}'"), None)
+                } else {
+                    (format!("'{}' is already restricted", word.pattern()), None)
+                }
+            }
+
+            "remove" => {
+                if update(gid, |s| s.remove_restricted_word(word.clone()).is_ok()) {
+                    (format!("Removed restricted word '{word}'"), None)
+                } else {
+                    (format!("'{word}' is not restricted"), None)
+                }
+            }
+
+            "action" => {
+                let action = action.unwrap_or_default();
+                update(gid, |s| s.set_filter_action(action));
+                (format!("Restricted words now default to: {action}"), None)
+            }
+
+            "list" => {
+                let settings = get(gid);
+                (
+                    format!(
+                        "Default action: {}\n{}",
+                        settings.filter_action,
+                        list_restricted_words(&settings.restricted_words, page)
+                    ),
+                    None,
+                )
+            }
+
+            "clear" => {
+                let count = update(gid, |s| {
+                    let count = s.restricted_words.len();
+                    s.set_restricted_words(Vec::new());
+                    count
+                });
+                (format!("Removed {count} restricted words"), None)
+            }
+
+            "import" => {
+                match file {
+                    Some(file) => (import_restricted_words(gid, file, replace).await, None),
+                    None => ("Error: No file provided".to_string(), None),
+                }
+            }
+
+            "export" => {
+                let settings = get(gid);
+                let words = RestrictedWords {
+                    words: settings.restricted_words,
+                    allowed: settings.allowed_words,
+                    action: Some(settings.filter_action),
+                };
+                match toml::to_string(&words) {
+                    Ok(x) => {
+                        (
+                            format!("Exported {} restricted words", words.words.len()),
+                            Some(("restricted_words.toml".to_string(), x.into_bytes())),
+                        )
+                    }
+                    Err(e) => (format!("Error exporting restricted words: {e}"), None),
+                }
+            }
+
+            _ => (format!("{} Failed!", sub.name), None),
+        };
+    }
+    ret
+}
+
+/// Format a page of restricted words
+fn list_restricted_words(words: &[RestrictedWord], page: usize) -> String
+{
+    if words.is_empty() {
+        return "This guild has no restricted words".to_string();
     }
 
-    pub fn remove_log(gid: &GuildId) -> String
+    let pages = (words.len() + FILTER_PAGE_SIZE - 1) / FILTER_PAGE_SIZE;
+    let page = page.min(pages);
+
+    let mut s = format!("Restricted words (page {page}/{pages}):\n");
+    for (i, word) in words
+        .iter()
+        .enumerate()
+        .skip((page - 1) * FILTER_PAGE_SIZE)
+        .take(FILTER_PAGE_SIZE)
     {
-        let (_, x) = SETTINGS.lock().unwrap().has_guild(gid);
+        let mut word = word.to_string();
+        if word.chars().count() > 100 {
+            word = format!("{}...", word.chars().take(100).collect::<String>());
+        }
+        s.push_str(&format!("{}. `{word}`\n", i + 1));
+    }
+    s
+}
+
+/// Add the restricted words in an attached file to a guild's list. The file
+/// is either in the same TOML format as the global restricted words, or
+/// contains one word per line.
+async fn import_restricted_words(gid: &GuildId, file: Attachment, replace: bool) -> String
+{
+    if file.size > FILTER_IMPORT_LIMIT {
+        return format!("Error: '{}' is too large to import", file.filename);
+    }
+
+    let contents = match file.download().await {
+        Ok(x) => String::from_utf8_lossy(&x).to_string(),
+        Err(e) => return format!("Error downloading '{}': {e}", file.filename),
+    };
+
+    let list = match toml::from_str::<RestrictedWords>(&contents) {
+        Ok(x) => x,
+        Err(_) => {
+            RestrictedWords {
+                words: contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(|l| RestrictedWord::Word(l.to_string()))
+                    .collect(),
+                allowed: Vec::new(),
+                action: None,
+            }
+        }
+    };
+
+    // Keep the file's default action with its entries
+    let (valid, invalid): (Vec<RestrictedWord>, Vec<RestrictedWord>) = list
+        .words
+        .into_iter()
+        .map(|w| {
+            match list.action {
+                Some(action) => w.or_action(action),
+                None => w,
+            }
+        })
+        .partition(|w| filtering::validate(w).is_ok());
+
+    let added = update(gid, |s| {
+        if replace {
+            s.set_restricted_words(Vec::new());
+        }
+        for word in list.allowed {
+            s.add_allowed_word(word);
+        }
+        valid
+            .into_iter()
+            .filter(|w| s.add_restricted_word(w.clone()))
+            .count()
+    });
 
-        if let Some(x) = x {
-            SETTINGS.lock().unwrap().guilds[x].settings.log_channel = None;
+    let mut s = format!("Imported {added} restricted words from '{}'", file.filename);
+    if !invalid.is_empty() {
+        s.push_str(&format!(
+            "\nSkipped {} invalid entries: {}",
+            invalid.len(),
+            invalid
+                .iter()
+                .take(10)
+                .map(|w| format!("`{}`", w.pattern()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    s
+}
+
+/// Run the `settings shadow_mode` subcommand
+pub fn shadow_mode(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let mut enabled = false;
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("enabled", Some(CommandDataOptionValue::Boolean(b))) => enabled = b,
+            _ => unreachable!(),
+        }
+    }
+
+    update(gid, |s| s.shadow_mode = enabled);
+    if enabled {
+        "Shadow mode is on. Automod hits are logged but no action is taken.".to_string()
+    } else {
+        "Shadow mode is off".to_string()
+    }
+}
+
+/// Run the `settings names` subcommand
+pub fn names(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let mut names = get(gid).names;
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("filter", Some(CommandDataOptionValue::Boolean(b))) => names.filter = b,
+            ("dehoist", Some(CommandDataOptionValue::Boolean(b))) => names.dehoist = b,
+            ("safe_nickname", Some(CommandDataOptionValue::String(n))) => {
+                let n = n.trim();
+                if n.is_empty() || n.chars().count() > 32 {
+                    return "Error: Nicknames must be between 1 and 32 characters".to_string();
+                }
+                names.safe_nickname = n.to_string();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let ret = format!(
+        "Filter names: {}\nDehoist names: {}\nSafe nickname: {}",
+        names.filter, names.dehoist, names.safe_nickname
+    );
+    update(gid, |s| s.names = names);
+    ret
+}
+
+/// Run the `settings raid` subcommand
+pub fn raid(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let mut raid = get(gid).raid;
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("enabled", Some(CommandDataOptionValue::Boolean(b))) => raid.enabled = b,
+            ("max_joins", Some(CommandDataOptionValue::Integer(i))) => {
+                raid.max_joins = i.max(1) as usize
+            }
+            ("interval", Some(CommandDataOptionValue::Integer(i))) => {
+                raid.interval = i.clamp(1, 3600) as u64
+            }
+            ("kick", Some(CommandDataOptionValue::Boolean(b))) => raid.kick = b,
+            ("timeout", Some(CommandDataOptionValue::String(t))) => {
+                match TimeoutTime::parse_timeout(&t) {
+                    Ok(t) => raid.timeout = t,
+                    Err(e) => return format!("Error: {e}"),
+                }
+            }
+            ("lockdown", Some(CommandDataOptionValue::String(t))) => {
+                match t.parse() {
+                    Ok(t) => raid.lockdown = t,
+                    Err(e) => return format!("Error: {e}"),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    update(gid, |s| s.raid = raid);
+
+    if raid.enabled {
+        format!(
+            "Raid detection is on. More than {} joins within {}s starts raid mode, where new \
+             members are {}. Raid mode lasts {}.",
+            raid.max_joins,
+            raid.interval,
+            if raid.kick {
+                "kicked".to_string()
+            } else {
+                format!("timed out for {}", raid.timeout)
+            },
+            if raid.lockdown.duration().num_seconds() > 0 {
+                raid.lockdown.to_string()
+            } else {
+                "until it's ended".to_string()
+            }
+        )
+    } else {
+        "Raid detection is off".to_string()
+    }
+}
+
+/// Run the `settings gate` subcommand
+pub fn gate(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let mut gate = get(gid).gate;
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("min_account_age", Some(CommandDataOptionValue::String(t))) => {
+                match t.parse() {
+                    Ok(t) => gate.min_account_age = t,
+                    Err(e) => return format!("Error: {e}"),
+                }
+            }
+            ("require_avatar", Some(CommandDataOptionValue::Boolean(b))) => gate.require_avatar = b,
+            ("quarantine_role", Some(CommandDataOptionValue::Role(r))) => {
+                gate.quarantine_role = Some(r.id)
+            }
+            ("kick", Some(CommandDataOptionValue::Boolean(b))) => {
+                if b {
+                    gate.quarantine_role = None
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    update(gid, |s| s.gate = gate);
+
+    format!(
+        "Minimum account age: {}\nRequire an avatar: {}\nMembers who fail are {}",
+        if gate.min_account_age.duration().num_seconds() > 0 {
+            gate.min_account_age.to_string()
         } else {
-            let mut s = config::Settings::new();
-            s.log_channel = None;
+            "none".to_string()
+        },
+        gate.require_avatar,
+        match gate.quarantine_role {
+            Some(r) => format!("given role {r}"),
+            None => "kicked".to_string(),
+        }
+    )
+}
+
+/// Run the `settings spam` subcommand
+pub fn spam(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let mut spam = get(gid).spam;
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("enabled", Some(CommandDataOptionValue::Boolean(b))) => spam.enabled = b,
+            ("interval", Some(CommandDataOptionValue::Integer(i))) => {
+                spam.interval = i.clamp(1, 300) as u64
+            }
+            ("max_messages", Some(CommandDataOptionValue::Integer(i))) => {
+                spam.max_messages = i.max(0) as usize
+            }
+            ("max_duplicates", Some(CommandDataOptionValue::Integer(i))) => {
+                spam.max_duplicates = i.max(0) as usize
+            }
+            ("max_attachments", Some(CommandDataOptionValue::Integer(i))) => {
+                spam.max_attachments = i.max(0) as usize
+            }
+            ("timeout", Some(CommandDataOptionValue::String(t))) => {
+                match TimeoutTime::parse_timeout(&t) {
+                    Ok(t) => spam.timeout = t,
+                    Err(e) => return format!("Error: {e}"),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    update(gid, |s| s.spam = spam);
+
+    if spam.enabled {
+        format!(
+            "Flood detection is on. Within {}s, members may send at most {} messages, {} \
+             identical messages or attachments and {} attachments (0 is unlimited). The first \
+             flood is warned, then repeat floods are timed out starting at {}.",
+            spam.interval,
+            spam.max_messages,
+            spam.max_duplicates,
+            spam.max_attachments,
+            spam.timeout
+        )
+    } else {
+        "Flood detection is off".to_string()
+    }
+}
+
+/// Run the `settings mentions` subcommand
+pub fn mentions(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let mut mentions = get(gid).mentions;
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("max_mentions", Some(CommandDataOptionValue::Integer(i))) => {
+                mentions.max_mentions = i.max(0) as usize
+            }
+            ("block_everyone", Some(CommandDataOptionValue::Boolean(b))) => {
+                mentions.block_everyone = b
+            }
+            ("timeout", Some(CommandDataOptionValue::String(t))) => {
+                match TimeoutTime::parse_timeout(&t) {
+                    Ok(t) => mentions.timeout = t,
+                    Err(e) => return format!("Error: {e}"),
+                }
+            }
+            ("exempt_role", Some(CommandDataOptionValue::Role(r))) => {
+                if !mentions.exempt_roles.contains(&r.id) {
+                    mentions.exempt_roles.push(r.id);
+                }
+            }
+            ("unexempt_role", Some(CommandDataOptionValue::Role(r))) => {
+                mentions.exempt_roles.retain(|id| *id != r.id)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let ret = format!(
+        "Mention limit: {}\nBlock @everyone: {}\nTimeout: {}\nExempt roles: {}",
+        match mentions.max_mentions {
+            0 => "none".to_string(),
+            x => x.to_string(),
+        },
+        mentions.block_everyone,
+        mentions.timeout,
+        if mentions.exempt_roles.is_empty() {
+            "none".to_string()
+        } else {
+            mentions
+                .exempt_roles
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
+
+    update(gid, |s| s.mentions = mentions);
+    ret
+}
+
+/// Run a `settings links` subcommand
+pub fn links(gid: &GuildId, group: CommandDataOption) -> String
+{
+    let mut ret = "Failed".to_string();
+    for sub in group.options {
+        let mut links = get(gid).links;
+        let mut domain = String::new();
+        let mut action = None;
+        let mut duration = None;
+        for opt in sub.options {
+            match (&*opt.name, opt.resolved) {
+                ("domain", Some(CommandDataOptionValue::String(d))) => domain = d,
+                ("block_invites", Some(CommandDataOptionValue::Boolean(b))) => {
+                    links.block_invites = b
+                }
+                ("block_phishing", Some(CommandDataOptionValue::Boolean(b))) => {
+                    links.block_phishing = b
+                }
+                ("action", Some(CommandDataOptionValue::String(a))) => action = Some(a),
+                ("duration", Some(CommandDataOptionValue::String(d))) => duration = Some(d),
+                _ => unreachable!(),
+            }
+        }
+
+        // Accept full links as well as bare domains
+        let domain = match automod::links::domains(&domain).into_iter().next() {
+            Some(d) => d,
+            None if sub.name == "set" || sub.name == "show" => String::new(),
+            None => return format!("Error: '{domain}' is not a domain"),
+        };
+
+        ret = match &*sub.name {
+            "allow" => {
+                links.denied_domains.retain(|d| *d != domain);
+                if links.allowed_domains.contains(&domain) {
+                    format!("'{domain}' is already allowed")
+                } else {
+                    links.allowed_domains.push(domain.clone());
+                    update(gid, |s| s.links = links);
+                    format!("Allowed links to '{domain}'")
+                }
+            }
+            "deny" => {
+                links.allowed_domains.retain(|d| *d != domain);
+                if links.denied_domains.contains(&domain) {
+                    format!("'{domain}' is already denied")
+                } else {
+                    links.denied_domains.push(domain.clone());
+                    update(gid, |s| s.links = links);
+                    format!("Denied links to '{domain}'")
+                }
+            }
+            "remove" => {
+                let count = links.allowed_domains.len() + links.denied_domains.len();
+                links.allowed_domains.retain(|d| *d != domain);
+                links.denied_domains.retain(|d| *d != domain);
+                if count == links.allowed_domains.len() + links.denied_domains.len() {
+                    format!("'{domain}' is not allowed or denied")
+                } else {
+                    update(gid, |s| s.links = links);
+                    format!("Removed '{domain}'")
+                }
+            }
+            "set" => {
+                let duration = match duration.map(|d| d.parse::<TimeoutTime>()).transpose() {
+                    Ok(x) => x,
+                    Err(e) => return format!("Error: {e}"),
+                };
+                if let Some(action) = action {
+                    match AutomodAction::from_name(&action, duration) {
+                        Ok(x) => links.action = x,
+                        Err(e) => return format!("Error: {e}"),
+                    }
+                }
+
+                update(gid, |s| s.links = links.clone());
+                format!(
+                    "Block invites: {}\nBlock phishing: {}\nAction: {}",
+                    links.block_invites, links.block_phishing, links.action
+                )
+            }
+            "show" => {
+                let list = |domains: &[String]| {
+                    if domains.is_empty() {
+                        "none".to_string()
+                    } else {
+                        domains.join(", ")
+                    }
+                };
+                format!(
+                    "Allowed domains: {}\nDenied domains: {}\nBlock invites: {}\nBlock phishing: \
+                     {}\nAction: {}",
+                    list(&links.allowed_domains),
+                    list(&links.denied_domains),
+                    links.block_invites,
+                    links.block_phishing,
+                    links.action
+                )
+            }
+            _ => format!("{} Failed!", sub.name),
+        };
+    }
+    ret
+}
+
+/// Run a `settings exempt` subcommand
+pub fn exempt(gid: &GuildId, group: CommandDataOption) -> String
+{
+    let mut ret = "Failed".to_string();
+    for sub in group.options {
+        let mut rule = AutomodRule::All;
+        let mut channel = None;
+        let mut role = None;
+        for opt in sub.options {
+            match (&*opt.name, opt.resolved) {
+                ("rule", Some(CommandDataOptionValue::String(r))) => {
+                    match r.parse() {
+                        Ok(r) => rule = r,
+                        Err(e) => return format!("Error: {e}"),
+                    }
+                }
+                ("channel", Some(CommandDataOptionValue::Channel(c))) => channel = Some(c.id),
+                ("role", Some(CommandDataOptionValue::Role(r))) => role = Some(r.id),
+                _ => unreachable!(),
+            }
+        }
+
+        if sub.name != "list" && channel.is_none() && role.is_none() {
+            return "Error: Give a channel or a role".to_string();
+        }
+
+        ret = match &*sub.name {
+            "add" => {
+                update(gid, |s| {
+                    let exemption = s.exemptions.get_mut(rule);
+                    if let Some(c) = channel.filter(|c| !exemption.channels.contains(c)) {
+                        exemption.channels.push(c);
+                    }
+                    if let Some(r) = role.filter(|r| !exemption.roles.contains(r)) {
+                        exemption.roles.push(r);
+                    }
+                });
+                format!(
+                    "Exempted {} from {rule} rules",
+                    describe_exemption(channel, role)
+                )
+            }
+            "remove" => {
+                update(gid, |s| {
+                    let exemption = s.exemptions.get_mut(rule);
+                    exemption.channels.retain(|c| Some(*c) != channel);
+                    exemption.roles.retain(|r| Some(*r) != role);
+                });
+                format!(
+                    "{} is no longer exempt from {rule} rules",
+                    describe_exemption(channel, role)
+                )
+            }
+            "list" => {
+                let exemptions = get(gid).exemptions;
+                let rules = [
+                    AutomodRule::All,
+                    AutomodRule::Words,
+                    AutomodRule::Links,
+                    AutomodRule::Spam,
+                    AutomodRule::Mentions,
+                    AutomodRule::Attachments,
+                ];
+
+                let mut lines = Vec::new();
+                for rule in rules {
+                    let exemption = exemptions.get(rule);
+                    let mut names: Vec<String> = exemption
+                        .channels
+                        .iter()
+                        .map(|c| format!("<#{c}>"))
+                        .collect();
+                    // Role IDs rather than mentions, so nobody is pinged
+                    names.extend(exemption.roles.iter().map(|r| format!("role {r}")));
+                    if !names.is_empty() {
+                        lines.push(format!("{rule}: {}", names.join(", ")));
+                    }
+                }
+
+                if lines.is_empty() {
+                    "Nothing is exempt from automod".to_string()
+                } else {
+                    lines.join("\n")
+                }
+            }
+            _ => format!("{} Failed!", sub.name),
+        };
+    }
+    ret
+}
+
+/// Run a `settings warnings` subcommand
+pub fn warnings(gid: &GuildId, group: CommandDataOption) -> String
+{
+    let mut ret = "Failed".to_string();
+    for sub in group.options {
+        let mut duration = None;
+        let mut name = String::new();
+        let mut points = 1;
+        for opt in sub.options {
+            match (&*opt.name, opt.resolved) {
+                ("duration", Some(CommandDataOptionValue::String(d))) => duration = Some(d),
+                ("name", Some(CommandDataOptionValue::String(n))) => name = n.trim().to_string(),
+                ("points", Some(CommandDataOptionValue::Integer(p))) => {
+                    points = match parse_points(p) {
+                        Ok(x) => x,
+                        Err(e) => return format!("Error: {e}"),
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
 
-            // Make a guild with the settings
-            SETTINGS.lock().unwrap().add_guild(*gid, s);
+        ret = match &*sub.name {
+            "expiry" => {
+                let duration = duration.unwrap_or_default();
+                let expiry = match duration.trim() {
+                    "0" => TimeoutTime::default(),
+                    d => {
+                        match d.parse() {
+                            Ok(x) => x,
+                            Err(e) => return format!("Error: {e}"),
+                        }
+                    }
+                };
+                update(gid, |s| s.warning_expiry = expiry);
+                if expiry.duration().num_seconds() > 0 {
+                    format!("Warnings expire after {expiry}. Expired warnings are still shown.")
+                } else {
+                    "Warnings never expire".to_string()
+                }
+            }
+            "add_preset" => {
+                if name.is_empty() {
+                    return "Error: No preset name given".to_string();
+                }
+                update(gid, |s| {
+                    s.warning_presets
+                        .retain(|p| !p.name.eq_ignore_ascii_case(&name));
+                    s.warning_presets.push(WarningPreset {
+                        name: name.clone(),
+                        points,
+                    });
+                });
+                format!("Warnings with the '{name}' preset are worth {points} points")
+            }
+            "remove_preset" => {
+                let removed = update(gid, |s| {
+                    let before = s.warning_presets.len();
+                    s.warning_presets
+                        .retain(|p| !p.name.eq_ignore_ascii_case(&name));
+                    s.warning_presets.len() != before
+                });
+                if removed {
+                    format!("Removed the '{name}' preset")
+                } else {
+                    format!("There is no preset '{name}'")
+                }
+            }
+            "presets" => {
+                let presets = get(gid).warning_presets;
+                if presets.is_empty() {
+                    "There are no warning presets. Warnings are worth 1 point.".to_string()
+                } else {
+                    presets
+                        .iter()
+                        .map(|p| format!("{}: {} points", p.name, p.points))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }
+            }
+            _ => format!("{} Failed!", sub.name),
+        };
+    }
+    ret
+}
+
+/// Run a `settings escalation` subcommand
+pub fn escalation(gid: &GuildId, group: CommandDataOption) -> String
+{
+    let mut ret = "Failed".to_string();
+    for sub in group.options {
+        let mut points = 0;
+        let mut action = String::new();
+        let mut duration = None;
+        for opt in sub.options {
+            match (&*opt.name, opt.resolved) {
+                ("points", Some(CommandDataOptionValue::Integer(p))) => {
+                    points = match parse_points(p) {
+                        Ok(x) => x,
+                        Err(e) => return format!("Error: {e}"),
+                    }
+                }
+                ("action", Some(CommandDataOptionValue::String(a))) => action = a,
+                ("duration", Some(CommandDataOptionValue::String(d))) => duration = Some(d),
+                _ => unreachable!(),
+            }
         }
-        SETTINGS
-            .lock()
-            .unwrap()
-            .save(SETTINGS_PATH.to_path_buf())
-            .unwrap();
-        "Removed log channel".to_string()
+
+        let mut steps = get(gid).escalation;
+        ret = match &*sub.name {
+            "add" => {
+                let action = match &*action {
+                    "timeout" => {
+                        let duration = match duration.map(|d| TimeoutTime::parse_timeout(&d)) {
+                            Some(Ok(x)) => x,
+                            Some(Err(e)) => return format!("Error: {e}"),
+                            None => return "Error: Timeouts need a duration".to_string(),
+                        };
+                        EscalationAction::Timeout(duration)
+                    }
+                    "kick" => EscalationAction::Kick,
+                    "ban" => EscalationAction::Ban,
+                    x => return format!("Error: Unknown action '{x}'"),
+                };
+                steps.retain(|s| s.points != points);
+                steps.push(EscalationStep { points, action });
+                format!("At {points} warning points: {action}")
+            }
+            "remove" => {
+                let before = steps.len();
+                steps.retain(|s| s.points != points);
+                if steps.len() == before {
+                    return format!("There is no step at {points} warning points");
+                }
+                format!("Removed the step at {points} warning points")
+            }
+            "list" => describe_escalation(&steps),
+            "clear" => {
+                steps.clear();
+                "Removed every escalation step. Warnings no longer lead to any action.".to_string()
+            }
+            _ => format!("{} Failed!", sub.name),
+        };
+
+        steps.sort_by_key(|s| s.points);
+        update(gid, |s| s.escalation = steps);
+    }
+    ret
+}
+
+/// Describe an escalation ladder, one step per line
+pub fn describe_escalation(steps: &[EscalationStep]) -> String
+{
+    if steps.is_empty() {
+        return "There are no escalation steps. Warnings don't lead to any action.".to_string();
+    }
+
+    let mut steps = steps.to_vec();
+    steps.sort_by_key(|s| s.points);
+    steps
+        .iter()
+        .map(|s| format!("At {} warning points: {}", s.points, s.action))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A guild's warning preset, by its name
+pub fn warning_preset(gid: &GuildId, name: &str) -> Option<WarningPreset>
+{
+    get(gid)
+        .warning_presets
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Run the `settings member_log` subcommand
+pub fn member_log(gid: &GuildId, sub: CommandDataOption) -> String
+{
+    let mut member_log = get(gid).member_log;
+    for opt in sub.options {
+        match (&*opt.name, opt.resolved) {
+            ("joins", Some(CommandDataOptionValue::Boolean(b))) => member_log.joins = b,
+            ("leaves", Some(CommandDataOptionValue::Boolean(b))) => member_log.leaves = b,
+            ("nicknames", Some(CommandDataOptionValue::Boolean(b))) => member_log.nicknames = b,
+            ("roles", Some(CommandDataOptionValue::Boolean(b))) => member_log.roles = b,
+            _ => unreachable!(),
+        }
+    }
+
+    update(gid, |s| s.member_log = member_log);
+    format!(
+        "Log joins: {}\nLog leaves: {}\nLog nickname changes: {}\nLog role changes: {}",
+        member_log.joins, member_log.leaves, member_log.nicknames, member_log.roles
+    )
+}
+
+/// Run a `settings message_log` subcommand
+pub fn message_log(gid: &GuildId, group: CommandDataOption) -> String
+{
+    let mut ret = "Failed".to_string();
+    for sub in group.options {
+        let mut log_settings = get(gid).message_log;
+        let mut channel = None;
+        for opt in sub.options {
+            match (&*opt.name, opt.resolved) {
+                ("deletes", Some(CommandDataOptionValue::Boolean(b))) => log_settings.deletes = b,
+                ("edits", Some(CommandDataOptionValue::Boolean(b))) => log_settings.edits = b,
+                ("channel", Some(CommandDataOptionValue::Channel(c))) => channel = Some(c.id),
+                _ => unreachable!(),
+            }
+        }
+
+        if let Some(c) = channel {
+            log_settings.watched_channels.retain(|x| *x != c);
+            log_settings.ignored_channels.retain(|x| *x != c);
+        }
+
+        ret = match (&*sub.name, channel) {
+            ("watch", Some(c)) => {
+                log_settings.watched_channels.push(c);
+                format!("Watching <#{c}>. Only watched channels are logged.")
+            }
+            ("ignore", Some(c)) => {
+                log_settings.ignored_channels.push(c);
+                format!("Ignoring <#{c}>")
+            }
+            ("reset", Some(c)) => format!("<#{c}> is no longer watched or ignored"),
+            ("set" | "show", _) => {
+                let channels = |list: &[ChannelId]| {
+                    if list.is_empty() {
+                        "none".to_string()
+                    } else {
+                        list.iter()
+                            .map(|c| format!("<#{c}>"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    }
+                };
+                format!(
+                    "Log deleted messages: {}\nLog edited messages: {}\nWatched channels: \
+                     {}\nIgnored channels: {}",
+                    log_settings.deletes,
+                    log_settings.edits,
+                    if log_settings.watched_channels.is_empty() {
+                        "all".to_string()
+                    } else {
+                        channels(&log_settings.watched_channels)
+                    },
+                    channels(&log_settings.ignored_channels)
+                )
+            }
+            _ => format!("{} Failed!", sub.name),
+        };
+
+        update(gid, |s| s.message_log = log_settings);
+    }
+    ret
+}
+
+/// Describe the channel and role named in a `settings exempt` subcommand
+fn describe_exemption(channel: Option<ChannelId>, role: Option<RoleId>) -> String
+{
+    match (channel, role) {
+        (Some(c), Some(r)) => format!("<#{c}> and role {r}"),
+        (Some(c), None) => format!("<#{c}>"),
+        (None, Some(r)) => format!("role {r}"),
+        (None, None) => "nothing".to_string(),
+    }
+}
+
+/// Run a `settings attachments` subcommand
+pub fn attachments(gid: &GuildId, group: CommandDataOption) -> String
+{
+    let mut ret = "Failed".to_string();
+    for sub in group.options {
+        let mut attachments = get(gid).attachments;
+        let mut extension = String::new();
+        let mut content_type = String::new();
+        let mut channel = None;
+        let mut kilobytes = 0;
+        let mut action = None;
+        let mut duration = None;
+        for opt in sub.options {
+            match (&*opt.name, opt.resolved) {
+                ("extension", Some(CommandDataOptionValue::String(e))) => {
+                    extension = automod::attachments::normalize_extension(&e)
+                }
+                ("content_type", Some(CommandDataOptionValue::String(t))) => {
+                    content_type = t.trim().to_lowercase()
+                }
+                ("channel", Some(CommandDataOptionValue::Channel(c))) => channel = Some(c.id),
+                ("kilobytes", Some(CommandDataOptionValue::Integer(k))) => {
+                    kilobytes = k.clamp(0, automod::attachments::MAX_SIZE as i64) as u64
+                }
+                ("action", Some(CommandDataOptionValue::String(a))) => action = Some(a),
+                ("duration", Some(CommandDataOptionValue::String(d))) => duration = Some(d),
+                _ => unreachable!(),
+            }
+        }
+
+        let place = match channel {
+            Some(c) => format!(" in <#{c}>"),
+            None => String::new(),
+        };
+
+        ret = match &*sub.name {
+            "deny_extension" | "allow_extension" => {
+                if extension.is_empty() {
+                    return "Error: No extension given".to_string();
+                }
+
+                let list = match channel {
+                    Some(c) => &mut attachments.channel_mut(c).denied_extensions,
+                    None => &mut attachments.denied_extensions,
+                };
+                list.retain(|e| *e != extension);
+                if sub.name == "deny_extension" {
+                    list.push(extension.clone());
+                    format!("Removing .{extension} attachments{place}")
+                } else {
+                    format!("No longer removing .{extension} attachments{place}")
+                }
+            }
+            "deny_type" | "allow_type" => {
+                if content_type.is_empty() {
+                    return "Error: No content type given".to_string();
+                }
+
+                let list = &mut attachments.denied_content_types;
+                list.retain(|t| *t != content_type);
+                if sub.name == "deny_type" {
+                    list.push(content_type.clone());
+                    format!("Removing {content_type} attachments")
+                } else {
+                    format!("No longer removing {content_type} attachments")
+                }
+            }
+            "max_size" => {
+                match channel {
+                    Some(c) => attachments.channel_mut(c).max_size = kilobytes,
+                    None => attachments.max_size = kilobytes,
+                }
+                if kilobytes == 0 {
+                    format!("Removed the attachment size limit{place}")
+                } else {
+                    format!("Attachments{place} may be at most {kilobytes}KB")
+                }
+            }
+            "action" => {
+                let duration = match duration.map(|d| d.parse::<TimeoutTime>()).transpose() {
+                    Ok(x) => x,
+                    Err(e) => return format!("Error: {e}"),
+                };
+                match AutomodAction::from_name(&action.unwrap_or_default(), duration) {
+                    Ok(x) => attachments.action = x,
+                    Err(e) => return format!("Error: {e}"),
+                }
+                format!("Denied attachments now: {}", attachments.action)
+            }
+            "show" => {
+                let list = |items: &[String]| {
+                    if items.is_empty() {
+                        "none".to_string()
+                    } else {
+                        items.join(", ")
+                    }
+                };
+                let size = |kb: u64| {
+                    if kb == 0 {
+                        "none".to_string()
+                    } else {
+                        format!("{kb}KB")
+                    }
+                };
+
+                let mut s = format!(
+                    "Denied extensions: {}\nDenied content types: {}\nSize limit: {}\nAction: {}",
+                    list(&attachments.denied_extensions),
+                    list(&attachments.denied_content_types),
+                    size(attachments.max_size),
+                    attachments.action
+                );
+                for c in &attachments.channels {
+                    s.push_str(&format!(
+                        "\n<#{}>: denied extensions: {}, size limit: {}",
+                        c.channel,
+                        list(&c.denied_extensions),
+                        size(c.max_size)
+                    ));
+                }
+                s
+            }
+            _ => format!("{} Failed!", sub.name),
+        };
+
+        // Drop channels that no longer have rules
+        attachments
+            .channels
+            .retain(|c| !c.denied_extensions.is_empty() || c.max_size > 0);
+        update(gid, |s| s.attachments = attachments);
     }
+    ret
 }
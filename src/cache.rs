@@ -0,0 +1,182 @@
+//! Bounded caches of recent guild messages and of guild members
+//!
+//! The gateway only sends the IDs of deleted messages and the new content of
+//! edited ones, so recent messages are kept here to show what was removed or
+//! changed. Likewise, it only sends a member's new nickname and roles, and
+//! nothing but the user of a member who left, so members are kept too.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use serenity::model::{
+    prelude::{ChannelId, GuildId, Member, Message, MessageId, RoleId, User, UserId},
+    timestamp::Timestamp,
+};
+
+/// The most messages cached at once, across every guild
+const MAX_CACHED: usize = 5_000;
+
+/// The most members cached at once, across every guild
+const MAX_CACHED_MEMBERS: usize = 50_000;
+
+lazy_static! {
+    static ref MESSAGES: Mutex<Cache<MessageId, CachedMessage>> =
+        Mutex::new(Cache::new(MAX_CACHED));
+    static ref MEMBERS: Mutex<Cache<(GuildId, UserId), CachedMember>> =
+        Mutex::new(Cache::new(MAX_CACHED_MEMBERS));
+}
+
+/// A cache that forgets its oldest entries once it's full
+struct Cache<K, V>
+{
+    /// The most entries kept at once
+    max: usize,
+
+    /// The cached entries
+    entries: HashMap<K, V>,
+
+    /// Cached keys, oldest first
+    order: VecDeque<K>,
+}
+
+impl<K: Copy + Eq + Hash, V> Cache<K, V>
+{
+    fn new(max: usize) -> Self
+    {
+        Self {
+            max,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Cache an entry, forgetting the oldest entry if the cache is full. An
+    /// entry that's already cached is replaced, and keeps its place. Returns
+    /// the replaced entry.
+    fn insert(&mut self, key: K, value: V) -> Option<V>
+    {
+        let replaced = self.entries.insert(key, value);
+        if replaced.is_some() {
+            return replaced;
+        }
+
+        self.order.push_back(key);
+        while self.order.len() > self.max {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        None
+    }
+
+    /// Forget an entry. Returns it, if it was cached.
+    fn remove(&mut self, key: &K) -> Option<V>
+    {
+        let removed = self.entries.remove(key);
+        if removed.is_some() {
+            self.order.retain(|x| x != key);
+        }
+        removed
+    }
+}
+
+/// What's kept of a message
+#[derive(Debug, Clone)]
+pub struct CachedMessage
+{
+    pub author: User,
+    pub channel_id: ChannelId,
+    pub content: String,
+
+    /// The file names of the message's attachments
+    pub attachments: Vec<String>,
+}
+
+impl From<&Message> for CachedMessage
+{
+    fn from(msg: &Message) -> Self
+    {
+        Self {
+            author: msg.author.clone(),
+            channel_id: msg.channel_id,
+            content: msg.content.clone(),
+            attachments: msg.attachments.iter().map(|a| a.filename.clone()).collect(),
+        }
+    }
+}
+
+/// Cache a message, forgetting the oldest message if the cache is full. A
+/// message that's already cached is replaced.
+pub fn insert(msg: &Message) { MESSAGES.lock().unwrap().insert(msg.id, msg.into()); }
+
+/// Replace a cached message's content. Returns the message as it was, if it
+/// was cached.
+pub fn update(id: &MessageId, content: &str) -> Option<CachedMessage>
+{
+    let mut cache = MESSAGES.lock().unwrap();
+    let cached = cache.entries.get_mut(id)?;
+    let before = cached.clone();
+    cached.content = content.to_string();
+    Some(before)
+}
+
+/// Forget a deleted message. Returns it, if it was cached.
+pub fn remove(id: &MessageId) -> Option<CachedMessage> { MESSAGES.lock().unwrap().remove(id) }
+
+/// What's kept of a member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedMember
+{
+    pub joined_at: Option<Timestamp>,
+    pub nick: Option<String>,
+    pub roles: Vec<RoleId>,
+}
+
+impl From<&Member> for CachedMember
+{
+    fn from(member: &Member) -> Self
+    {
+        Self {
+            joined_at: member.joined_at,
+            nick: member.nick.clone(),
+            roles: member.roles.clone(),
+        }
+    }
+}
+
+/// Cache a member, forgetting the member cached longest ago if the cache is
+/// full. Returns the member as they were, if they were cached.
+pub fn insert_member(gid: GuildId, uid: UserId, member: CachedMember) -> Option<CachedMember>
+{
+    MEMBERS.lock().unwrap().insert((gid, uid), member)
+}
+
+/// Forget a member who left. Returns them, if they were cached.
+pub fn remove_member(gid: GuildId, uid: UserId) -> Option<CachedMember>
+{
+    MEMBERS.lock().unwrap().remove(&(gid, uid))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_eviction()
+    {
+        let mut cache = Cache::new(2);
+        assert_eq!(cache.insert(1, "a"), None);
+        assert_eq!(cache.insert(2, "b"), None);
+        assert_eq!(cache.insert(1, "c"), Some("a"));
+        assert_eq!(cache.insert(3, "d"), None);
+        assert!(!cache.entries.contains_key(&1));
+        assert_eq!(cache.remove(&2), Some("b"));
+        assert_eq!(cache.remove(&2), None);
+        assert_eq!(cache.order, vec![3]);
+    }
+}
@@ -7,6 +7,7 @@ use serenity::{
             application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
             InteractionResponseType,
         },
+        AttachmentType,
     },
     prelude::Context,
 };
@@ -15,8 +16,8 @@ use tokio::sync::Mutex;
 
 use crate::{
     builtins::{self, meta, moderation},
-    config::WarnBehavior,
-    CONFIG,
+    config::LogCategory,
+    warnings, CONFIG,
 };
 
 use lazy_static::lazy_static;
@@ -63,10 +64,15 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
         cmd_count.0 += 1
     }
 
+    // A file to attach to the response
+    let mut file: Option<(String, Vec<u8>)> = None;
+
     let command_name = command.data.name.as_str();
     let content = match command_name {
         "meta" => meta::meta(),
 
+        "reload" => builtins::owner::reload(&context, &command.user.id).await,
+
         "quote" => {
             let mut ret = "Failed".to_string();
             for option in command.data.options.clone() {
@@ -198,8 +204,11 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
 
                                 let guild_id = command.guild_id.unwrap();
 
-                                ret =
-                                    moderation::kick::run(&context, &guild_id, &user, reason).await;
+                                let moderator = moderation::Moderator::Member(command.user.clone());
+                                ret = moderation::kick::run(
+                                    &context, &guild_id, &user, reason, &moderator,
+                                )
+                                .await;
                             }
 
                             "ban" => {
@@ -239,14 +248,18 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
 
                                 let guild_id = command.guild_id.unwrap();
 
-                                ret =
-                                    moderation::ban::run(&context, &guild_id, &user, reason, days)
-                                        .await;
+                                let moderator = moderation::Moderator::Member(command.user.clone());
+                                ret = moderation::ban::run(
+                                    &context, &guild_id, &user, reason, days, &moderator,
+                                )
+                                .await;
                             }
 
                             "warn" => {
                                 let mut user = None;
-                                let mut reason = "No reason provided.".to_string();
+                                let mut reason = None;
+                                let mut points = None;
+                                let mut preset = None;
                                 for option in option.options {
                                     let opt = option.resolved.unwrap();
 
@@ -258,7 +271,17 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                                         }
                                         "reason" => {
                                             if let CommandDataOptionValue::String(r) = opt {
-                                                reason = r.clone();
+                                                reason = Some(r);
+                                            }
+                                        }
+                                        "points" => {
+                                            if let CommandDataOptionValue::Integer(p) = opt {
+                                                points = Some(p);
+                                            }
+                                        }
+                                        "preset" => {
+                                            if let CommandDataOptionValue::String(p) = opt {
+                                                preset = Some(p);
                                             }
                                         }
 
@@ -274,12 +297,44 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
 
                                 let guild_id = command.guild_id.unwrap();
 
-                                ret =
-                                    moderation::warn::warn(&context, &guild_id, user, reason).await
+                                let mut points = match points.map(warnings::parse_points) {
+                                    Some(Ok(p)) => Some(p),
+                                    Some(Err(e)) => {
+                                        ret = format!("Error: {e}");
+                                        break;
+                                    }
+                                    None => None,
+                                };
+
+                                // A preset gives the points, and the reason if there isn't one
+                                if let Some(name) = preset {
+                                    match builtins::settings::warning_preset(&guild_id, &name) {
+                                        Some(p) => {
+                                            points = points.or(Some(p.points));
+                                            reason = reason.or(Some(p.name));
+                                        }
+                                        None => {
+                                            ret = format!("Error: There is no preset '{name}'");
+                                            break;
+                                        }
+                                    }
+                                }
+
+                                let moderator = moderation::Moderator::Member(command.user.clone());
+                                ret = moderation::warn::warn(
+                                    &context,
+                                    &guild_id,
+                                    user,
+                                    reason.unwrap_or_else(|| "No reason provided.".to_string()),
+                                    points.unwrap_or(1),
+                                    &moderator,
+                                )
+                                .await
                             }
 
                             "get_warnings" => {
                                 let mut user = None;
+                                let mut show_removed = false;
                                 for option in option.options {
                                     let opt = option.resolved.unwrap();
 
@@ -289,6 +344,11 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                                                 user = Some(u);
                                             }
                                         }
+                                        "show_removed" => {
+                                            if let CommandDataOptionValue::Boolean(b) = opt {
+                                                show_removed = b;
+                                            }
+                                        }
                                         _ => unreachable!(),
                                     }
                                 }
@@ -301,11 +361,12 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
 
                                 let guild_id = command.guild_id.unwrap();
 
-                                ret = moderation::warn::get_warns(&guild_id, user)
+                                ret = moderation::warn::get_warns(&guild_id, user, show_removed)
                             }
 
-                            "remove_warns" => {
+                            "remove_warnings" => {
                                 let mut user = None;
+                                let mut reason = "No reason provided.".to_string();
                                 for option in option.options {
                                     let opt = option.resolved.unwrap();
 
@@ -315,6 +376,11 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                                                 user = Some(u);
                                             }
                                         }
+                                        "reason" => {
+                                            if let CommandDataOptionValue::String(r) = opt {
+                                                reason = r.clone();
+                                            }
+                                        }
                                         _ => unreachable!(),
                                     }
                                 }
@@ -327,13 +393,48 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
 
                                 let guild_id = command.guild_id.unwrap();
 
-                                ret = moderation::warn::remove_warns(&guild_id, user)
+                                let moderator = moderation::Moderator::Member(command.user.clone());
+                                ret = moderation::warn::remove_warns(
+                                    &context, &guild_id, user, reason, &moderator,
+                                )
+                                .await
+                            }
+
+                            "remove_warning" | "edit_warning" => {
+                                let mut id = 0;
+                                let mut reason = "No reason provided.".to_string();
+                                for option in option.options {
+                                    match (&*option.name, option.resolved) {
+                                        ("id", Some(CommandDataOptionValue::Integer(i))) => {
+                                            id = i.max(0) as u64
+                                        }
+                                        ("reason", Some(CommandDataOptionValue::String(r))) => {
+                                            reason = r
+                                        }
+                                        _ => unreachable!(),
+                                    }
+                                }
+
+                                let guild_id = command.guild_id.unwrap();
+                                let moderator = moderation::Moderator::Member(command.user.clone());
+                                ret = if option.name == "remove_warning" {
+                                    moderation::warn::remove_warning(
+                                        &context, &guild_id, id, reason, &moderator,
+                                    )
+                                    .await
+                                } else {
+                                    moderation::warn::edit_warning(
+                                        &context, &guild_id, id, reason, &moderator,
+                                    )
+                                    .await
+                                };
                             }
 
                             "timeout" => {
                                 let mut user = None;
                                 let mut time: moderation::timeout::TimeoutTime =
                                     moderation::timeout::TimeoutTime::default();
+                                let mut reason = None;
                                 for option in option.options {
                                     let opt = option.resolved.unwrap();
 
@@ -363,6 +464,11 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                                                 time.seconds = Some(t)
                                             }
                                         }
+                                        "reason" => {
+                                            if let CommandDataOptionValue::String(r) = opt {
+                                                reason = Some(r)
+                                            }
+                                        }
                                         _ => unreachable!(),
                                     }
                                 }
@@ -375,12 +481,15 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                                 if time.is_none() {
                                     ret = "Error: No units of time were provided!".to_string()
                                 } else {
+                                    let moderator =
+                                        moderation::Moderator::Member(command.user.clone());
                                     ret = moderation::timeout::timeout(
                                         &context,
                                         &command.guild_id.unwrap(),
                                         user.unwrap(),
                                         time,
-                                        false,
+                                        &moderator,
+                                        reason.as_deref(),
                                     )
                                     .await
                                     .unwrap()
@@ -410,7 +519,11 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
 
                                 let guild_id = command.guild_id.unwrap();
 
-                                ret = moderation::timeout::release(&context, &guild_id, user).await;
+                                let moderator = moderation::Moderator::Member(command.user.clone());
+                                ret = moderation::timeout::release(
+                                    &context, &guild_id, user, &moderator,
+                                )
+                                .await;
                             }
 
                             _ => {
@@ -418,6 +531,26 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                             }
                         }
                     }
+                    CommandOptionType::SubCommandGroup => {
+                        let guild_id = command.guild_id.unwrap();
+                        for sub in option.options {
+                            ret = match &*sub.name {
+                                "end" => crate::automod::raid::end(&context, &guild_id).await,
+                                "ban" => {
+                                    let mut reason = "Joined during a raid".to_string();
+                                    for opt in sub.options {
+                                        if let ("reason", Some(CommandDataOptionValue::String(r))) =
+                                            (&*opt.name, opt.resolved)
+                                        {
+                                            reason = r;
+                                        }
+                                    }
+                                    crate::automod::raid::ban(&context, &guild_id, reason).await
+                                }
+                                _ => format!("{} Failed!", sub.name),
+                            };
+                        }
+                    }
                     _ => unreachable!(),
                 }
             }
@@ -550,6 +683,26 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
             ret
         }
 
+        "automod" => {
+            let mut ret = "Failed".to_string();
+            let guild_id = command.guild_id.unwrap();
+            for option in command.data.options.clone() {
+                match &*option.name {
+                    "test" => {
+                        for opt in option.options {
+                            if let ("text", Some(CommandDataOptionValue::String(text))) =
+                                (&*opt.name, opt.resolved)
+                            {
+                                ret = crate::automod::test(&context, &guild_id, &text).await;
+                            }
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            ret
+        }
+
         "settings" => {
             let mut ret = "Failed".to_string();
             let guild_id = command.guild_id.unwrap();
@@ -557,24 +710,29 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                 match option.kind {
                     CommandOptionType::SubCommand => {
                         match &*option.name {
-                            "set_log" => {
+                            "set_log" | "remove_log" => {
+                                let (mut channel, mut category) = (None, Ok(LogCategory::All));
                                 for opt in option.options {
-                                    match &*opt.name {
-                                        "channel" => {
-                                            if let CommandDataOptionValue::Channel(c) =
-                                                opt.resolved.unwrap()
-                                            {
-                                                ret = builtins::settings::Log::set_log(c, &guild_id)
-                                            }
+                                    match (&*opt.name, opt.resolved) {
+                                        ("channel", Some(CommandDataOptionValue::Channel(c))) => {
+                                            channel = Some(c)
+                                        }
+                                        ("category", Some(CommandDataOptionValue::String(c))) => {
+                                            category = c.parse()
                                         }
                                         _ => unreachable!(),
                                     }
                                 }
-                            }
-                            "remove_log" => {
-                                let guild_id = command.guild_id.unwrap();
-                                builtins::settings::Log::remove_log(&guild_id);
-                                ret = "Removed logging channel".to_string()
+
+                                ret = match (channel, category) {
+                                    (_, Err(e)) => format!("Error: {e}"),
+                                    (Some(c), Ok(category)) => {
+                                        builtins::settings::Log::set_log(c, category, &guild_id)
+                                    }
+                                    (None, Ok(category)) => {
+                                        builtins::settings::Log::remove_log(category, &guild_id)
+                                    }
+                                };
                             }
 
                             "set_wiki_limit" => {
@@ -595,47 +753,75 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
                                 ret = builtins::settings::set_wiki_limit(&guild_id, limit);
                             }
 
+                            "names" => {
+                                ret = builtins::settings::names(&guild_id, option);
+                            }
+
+                            "member_log" => {
+                                ret = builtins::settings::member_log(&guild_id, option);
+                            }
+
+                            "raid" => {
+                                ret = builtins::settings::raid(&guild_id, option);
+                            }
+
+                            "gate" => {
+                                ret = builtins::settings::gate(&guild_id, option);
+                            }
+
+                            "shadow_mode" => {
+                                ret = builtins::settings::shadow_mode(&guild_id, option);
+                            }
+
+                            "spam" => {
+                                ret = builtins::settings::spam(&guild_id, option);
+                            }
+
+                            "mentions" => {
+                                ret = builtins::settings::mentions(&guild_id, option);
+                            }
+
                             "set_warn_behavior" => {
-                                let (mut count, mut behavior) = (255_u8, "nothing".to_string());
-                                for opt in option.options {
-                                    match &*opt.name {
-                                        "behavior" => {
-                                            if let CommandDataOptionValue::String(s) =
-                                                opt.resolved.unwrap()
-                                            {
-                                                behavior = s;
-                                            }
-                                        }
+                                ret = builtins::settings::set_warn_behavior(&guild_id, option);
+                            }
 
-                                        "max" => {
-                                            if let CommandDataOptionValue::Integer(c) =
-                                                opt.resolved.unwrap()
-                                            {
-                                                // Ensure sane numbers
-                                                count = c.clamp(0, 255) as u8;
-                                            }
-                                        }
-                                        _ => unreachable!(),
-                                    }
-                                }
+                            _ => {
+                                ret = format!("{} Failed!", option.name);
+                            }
+                        }
+                    }
+                    CommandOptionType::SubCommandGroup => {
+                        match &*option.name {
+                            "filter" => {
+                                (ret, file) = builtins::settings::filter(&guild_id, option).await;
+                            }
 
-                                let w = match behavior.to_lowercase().as_str() {
-                                    "ban" => WarnBehavior::Ban(count),
-                                    "timeout" => {
-                                        WarnBehavior::Timeout {
-                                            warning_count: count,
-                                            duration: moderation::timeout::TimeoutTime {
-                                                seconds: None,
-                                                minutes: None,
-                                                hours: None,
-                                                days: Some(1),
-                                            },
-                                        }
-                                    }
-                                    _ => WarnBehavior::Nothing,
-                                };
+                            "allowlist" => {
+                                ret = builtins::settings::allowlist(&guild_id, option);
+                            }
+
+                            "links" => {
+                                ret = builtins::settings::links(&guild_id, option);
+                            }
+
+                            "exempt" => {
+                                ret = builtins::settings::exempt(&guild_id, option);
+                            }
 
-                                ret = builtins::settings::set_warning_behavior(&guild_id, w);
+                            "attachments" => {
+                                ret = builtins::settings::attachments(&guild_id, option);
+                            }
+
+                            "warnings" => {
+                                ret = builtins::settings::warnings(&guild_id, option);
+                            }
+
+                            "escalation" => {
+                                ret = builtins::settings::escalation(&guild_id, option);
+                            }
+
+                            "message_log" => {
+                                ret = builtins::settings::message_log(&guild_id, option);
                             }
 
                             _ => {
@@ -656,7 +842,15 @@ pub async fn run(context: Context, command: ApplicationCommandInteraction)
         .create_interaction_response(&context.http, |response| {
             response
                 .kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|message| message.content(content))
+                .interaction_response_data(|message| {
+                    if let Some((filename, data)) = file {
+                        message.add_file(AttachmentType::Bytes {
+                            data: data.into(),
+                            filename,
+                        });
+                    }
+                    message.content(content)
+                })
         })
         .await
     {
@@ -1,8 +1,10 @@
 use log::info;
 use serde_derive::*;
 
+use serenity::model::prelude::ChannelId;
 use serenity::model::prelude::GuildId;
 use serenity::model::prelude::PartialChannel;
+use serenity::model::prelude::RoleId;
 use std::{
     fs::{self, create_dir_all, read_to_string},
     io::{Error, ErrorKind},
@@ -13,7 +15,268 @@ use structstruck::strike;
 #[derive(Deserialize, Clone, Serialize)]
 pub struct RestrictedWords
 {
-    pub words: Vec<String>,
+    pub words: Vec<RestrictedWord>,
+
+    /// Words that never count as a restricted word, even when a restricted
+    /// word matches inside of them
+    #[serde(default)]
+    pub allowed: Vec<String>,
+
+    /// The action taken for entries that don't set their own (Default: delete)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<AutomodAction>,
+}
+
+/// A restricted word entry. Either a plain word, or a table describing how the
+/// pattern is matched.
+///
+/// ```toml
+/// words = [
+///     "foo",
+///     { pattern = "b*r", mode = "glob" },
+///     { pattern = "baz", mode = "substring", whole_word = true, action = "warn" },
+/// ]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum RestrictedWord
+{
+    /// A word matched against whole words of a message
+    Word(String),
+
+    /// A pattern with an explicit match mode
+    Rule
+    {
+        pattern: String,
+
+        #[serde(default)]
+        mode: MatchMode,
+
+        /// Only match whole words, regardless of the mode
+        #[serde(default)]
+        whole_word: bool,
+
+        /// What to do when the entry matches, instead of the list's action
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        action: Option<AutomodAction>,
+    },
+}
+
+impl RestrictedWord
+{
+    /// Create an entry, using the plain word form when no options are needed
+    pub fn new(
+        pattern: String,
+        mode: MatchMode,
+        whole_word: bool,
+        action: Option<AutomodAction>,
+    ) -> Self
+    {
+        if mode == MatchMode::Word && action.is_none() {
+            Self::Word(pattern)
+        } else {
+            Self::Rule {
+                pattern,
+                mode,
+                whole_word,
+                action,
+            }
+        }
+    }
+
+    /// The word or pattern being matched
+    pub fn pattern(&self) -> &str
+    {
+        match self {
+            Self::Word(w) => w,
+            Self::Rule { pattern, .. } => pattern,
+        }
+    }
+
+    /// How the pattern is matched
+    pub fn mode(&self) -> MatchMode
+    {
+        match self {
+            Self::Word(_) => MatchMode::Word,
+            Self::Rule { mode, .. } => *mode,
+        }
+    }
+
+    /// Whether the pattern only matches whole words
+    pub fn whole_word(&self) -> bool
+    {
+        match self {
+            Self::Word(_) => true,
+            Self::Rule {
+                mode, whole_word, ..
+            } => *whole_word || *mode == MatchMode::Word,
+        }
+    }
+
+    /// The action set on this entry, if any
+    pub fn action(&self) -> Option<AutomodAction>
+    {
+        match self {
+            Self::Word(_) => None,
+            Self::Rule { action, .. } => *action,
+        }
+    }
+
+    /// Set the entry's action to `default` if it doesn't have its own
+    pub fn or_action(self, default: AutomodAction) -> Self
+    {
+        let action = Some(self.action().unwrap_or(default));
+        match self {
+            Self::Word(pattern) => {
+                Self::Rule {
+                    pattern,
+                    mode: MatchMode::Word,
+                    whole_word: true,
+                    action,
+                }
+            }
+            Self::Rule {
+                pattern,
+                mode,
+                whole_word,
+                ..
+            } => {
+                Self::Rule {
+                    pattern,
+                    mode,
+                    whole_word,
+                    action,
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for RestrictedWord
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "{} ({}", self.pattern(), self.mode())?;
+        if self.whole_word() && self.mode() != MatchMode::Word {
+            write!(f, ", whole word")?;
+        }
+        if let Some(action) = self.action() {
+            write!(f, ", {action}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// What automod does when one of its rules matches a message
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum AutomodAction
+{
+    /// Only report the message to the log channel
+    Log,
+
+    /// Delete the message
+    #[default]
+    Delete,
+
+    /// Delete the message and warn its author
+    Warn,
+
+    /// Delete the message and timeout its author
+    Timeout(crate::builtins::moderation::timeout::TimeoutTime),
+
+    /// Delete the message and kick its author
+    Kick,
+}
+
+impl AutomodAction
+{
+    /// Create an action from its name. Timeouts need a duration.
+    pub fn from_name(
+        name: &str,
+        duration: Option<crate::builtins::moderation::timeout::TimeoutTime>,
+    ) -> Result<Self, String>
+    {
+        match name.trim().to_lowercase().as_str() {
+            "log" => Ok(Self::Log),
+            "delete" => Ok(Self::Delete),
+            "warn" => Ok(Self::Warn),
+            "kick" => Ok(Self::Kick),
+            "timeout" => {
+                match duration {
+                    Some(d) if !d.is_none() => d.check_timeout().map(|_| Self::Timeout(d)),
+                    _ => Err("A duration is needed to timeout".to_string()),
+                }
+            }
+            x => Err(format!("Unknown action '{x}'")),
+        }
+    }
+
+    /// Whether the action removes the message
+    pub fn deletes(&self) -> bool { !matches!(self, Self::Log) }
+}
+
+impl std::fmt::Display for AutomodAction
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            Self::Log => write!(f, "log"),
+            Self::Delete => write!(f, "delete"),
+            Self::Warn => write!(f, "delete and warn"),
+            Self::Timeout(d) => write!(f, "delete and timeout for {d}"),
+            Self::Kick => write!(f, "delete and kick"),
+        }
+    }
+}
+
+/// How a restricted word is matched against message content
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchMode
+{
+    /// Matches a whole word
+    #[default]
+    Word,
+
+    /// Matches anywhere inside a word
+    Substring,
+
+    /// Matches a whole word with `*` and `?` wildcards
+    Glob,
+
+    /// Matches a regular expression against the whole message
+    Regex,
+}
+
+impl std::str::FromStr for MatchMode
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s.trim().to_lowercase().as_str() {
+            "word" => Ok(Self::Word),
+            "substring" => Ok(Self::Substring),
+            "glob" => Ok(Self::Glob),
+            "regex" => Ok(Self::Regex),
+            x => Err(format!("Unknown match mode '{x}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for MatchMode
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        let s = match self {
+            Self::Word => "word",
+            Self::Substring => "substring",
+            Self::Glob => "glob",
+            Self::Regex => "regex",
+        };
+        write!(f, "{s}")
+    }
 }
 
 impl RestrictedWords
@@ -28,6 +291,69 @@ impl RestrictedWords
 
         Ok(words)
     }
+
+    /// Add a word to the allowlist of a restricted words file. Returns false
+    /// if it was already allowed.
+    pub fn allow(path: PathBuf, word: &str) -> Result<bool, Error>
+    {
+        let word = word.to_lowercase();
+        Self::edit_allowlist(path, |allowed| {
+            if allowed.iter().any(|w| w.as_str() == Some(&*word)) {
+                false
+            } else {
+                allowed.push(word.as_str());
+                true
+            }
+        })
+    }
+
+    /// Remove a word from the allowlist of a restricted words file. Returns
+    /// false if it wasn't allowed.
+    pub fn disallow(path: PathBuf, word: &str) -> Result<bool, Error>
+    {
+        let word = word.to_lowercase();
+        Self::edit_allowlist(path, |allowed| {
+            let found: Vec<usize> = allowed
+                .iter()
+                .enumerate()
+                .filter(|(_, w)| w.as_str() == Some(&*word))
+                .map(|(i, _)| i)
+                .collect();
+            // Remove from the back, so the earlier positions stay the same
+            for i in found.iter().rev() {
+                allowed.remove(*i);
+            }
+            allowed.fmt();
+            !found.is_empty()
+        })
+    }
+
+    /// Change the allowlist of a restricted words file in place, so the
+    /// operator's comments and ordering are kept. The file is only written
+    /// when `f` returns true.
+    fn edit_allowlist(
+        path: PathBuf,
+        f: impl FnOnce(&mut toml_edit::Array) -> bool,
+    ) -> Result<bool, Error>
+    {
+        let contents = read_to_string(&path)?;
+        let mut document: toml_edit::Document = match contents.parse() {
+            Ok(x) => x,
+            Err(x) => return Err(Error::new(ErrorKind::Other, x)),
+        };
+
+        let allowed = document
+            .entry("allowed")
+            .or_insert(toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .ok_or_else(|| Error::new(ErrorKind::Other, "'allowed' isn't a list of words"))?;
+        if !f(allowed) {
+            return Ok(false);
+        }
+
+        fs::write(path, document.to_string())?;
+        Ok(true)
+    }
 }
 
 strike! {
@@ -44,13 +370,31 @@ strike! {
                 /// The maximum number of characters to return from the wiki command
                 pub wiki_limit: Option<usize>,
 
-                /// The channel to log events
+                /// The channel to log events. Categories without their own channel
+                /// are logged here.
                 pub log_channel: Option<PartialChannel>,
 
+                /// Channels for particular categories of events, in place of
+                /// `log_channel`
+                #[serde(default)]
+                pub log_channels: LogChannels,
+
                 /// Additional restricted words local to a guild
-                pub restricted_words: Vec<String>,
+                pub restricted_words: Vec<RestrictedWord>,
+
+                /// Words local to a guild that never count as a restricted word
+                #[serde(default)]
+                pub allowed_words: Vec<String>,
 
-                /// How to behave when a warning limit is reached
+                /// The action taken for the guild's restricted words that don't set
+                /// their own
+                #[serde(default)]
+                pub filter_action: AutomodAction,
+
+                /// How to behave when a warning limit was reached, before the
+                /// escalation ladder. Converted into `escalation` when the settings
+                /// load.
+                #[serde(default, skip_serializing)]
                 pub warning_behavior:
                 #[derive(Copy)]
                 pub enum WarnBehavior{
@@ -59,27 +403,723 @@ strike! {
                     #[default]
                     Nothing,
 
-                    /// Ban a user after a specified number of warnings
-                    Ban(u8),
+                    /// Ban a user after a specified number of warning points
+                    Ban(u32),
 
-                    /// Kick a user after a specified number of warnings
-                    Kick(u8),
+                    /// Kick a user after a specified number of warning points
+                    Kick(u32),
 
-                    /// Timeout a user after a specified number of warnings for a specified
-                    /// lentgh of time
+                    /// Timeout a user after a specified number of warning points for a
+                    /// specified lentgh of time
                     Timeout{
-                        /// The number of warnings
-                        warning_count: u8,
+                        /// The number of warning points
+                        warning_count: u32,
 
                         /// How long to timeout for
                         duration: crate::builtins::moderation::timeout::TimeoutTime,
                     },
                 },
+
+                /// What happens as a member's warning points rise
+                #[serde(default)]
+                pub escalation: Vec<EscalationStep>,
+
+                /// How long warnings count towards the escalation ladder. Zero for
+                /// forever.
+                #[serde(default)]
+                pub warning_expiry: crate::builtins::moderation::timeout::TimeoutTime,
+
+                /// Named point values for warnings, such as "spam" for 1 point
+                #[serde(default)]
+                pub warning_presets: Vec<WarningPreset>,
+
+                /// Message flood detection
+                #[serde(default)]
+                pub spam: SpamSettings,
+
+                /// Mass mention protection
+                #[serde(default)]
+                pub mentions: MentionSettings,
+
+                /// Link and invite filtering
+                #[serde(default)]
+                pub links: LinkSettings,
+
+                /// Channels and roles that bypass automod rules
+                #[serde(default)]
+                pub exemptions: Exemptions,
+
+                /// Whether automod only logs its hits instead of acting on them
+                #[serde(default)]
+                pub shadow_mode: bool,
+
+                /// Nickname and username screening
+                #[serde(default)]
+                pub names: NameSettings,
+
+                /// Join-rate raid detection
+                #[serde(default)]
+                pub raid: RaidSettings,
+
+                /// Account age and avatar requirements for new members
+                #[serde(default)]
+                pub gate: GateSettings,
+
+                /// Attachment filtering
+                #[serde(default)]
+                pub attachments: AttachmentSettings,
+
+                /// Logging of deleted and edited messages
+                #[serde(default)]
+                pub message_log: MessageLogSettings,
+
+                /// Logging of members joining, leaving and changing
+                #[serde(default)]
+                pub member_log: MemberLogSettings,
             }
         }>
     }
 }
 
+/// Thresholds for detecting message floods from a single member
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpamSettings
+{
+    /// Whether flood detection is on
+    pub enabled: bool,
+
+    /// The length of the sliding window, in seconds
+    pub interval: u64,
+
+    /// The most messages a member may send within the window
+    pub max_messages: usize,
+
+    /// The most times a member may send the same message, or the same
+    /// attachment, within the window
+    pub max_duplicates: usize,
+
+    /// The most attachments a member may send within the window
+    pub max_attachments: usize,
+
+    /// How long to timeout a member who keeps flooding after being warned.
+    /// Doubles with each repeat offence.
+    pub timeout: crate::builtins::moderation::timeout::TimeoutTime,
+}
+
+impl Default for SpamSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            enabled: false,
+            interval: 5,
+            max_messages: 6,
+            max_duplicates: 3,
+            max_attachments: 6,
+            timeout: crate::builtins::moderation::timeout::TimeoutTime {
+                minutes: Some(10),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Limits on the mentions in a single message
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MentionSettings
+{
+    /// The most distinct users and roles a message may mention. Zero for no
+    /// limit.
+    pub max_mentions: usize,
+
+    /// Whether to remove `@everyone` and `@here` from members who aren't
+    /// allowed to use them
+    pub block_everyone: bool,
+
+    /// Roles whose members may mention freely
+    pub exempt_roles: Vec<RoleId>,
+
+    /// How long to timeout the author of a mass mention
+    pub timeout: crate::builtins::moderation::timeout::TimeoutTime,
+}
+
+impl Default for MentionSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            max_mentions: 0,
+            block_everyone: false,
+            exempt_roles: Vec::new(),
+            timeout: crate::builtins::moderation::timeout::TimeoutTime {
+                hours: Some(1),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Which links may be posted
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LinkSettings
+{
+    /// Domains that are always allowed, along with their subdomains
+    pub allowed_domains: Vec<String>,
+
+    /// Domains that are never allowed, along with their subdomains
+    pub denied_domains: Vec<String>,
+
+    /// Whether to remove invites to other servers
+    pub block_invites: bool,
+
+    /// Whether to remove links to domains on the phishing list
+    pub block_phishing: bool,
+
+    /// What to do when a link is removed
+    pub action: AutomodAction,
+}
+
+impl Default for LinkSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            allowed_domains: Vec::new(),
+            denied_domains: Vec::new(),
+            block_invites: false,
+            block_phishing: true,
+            action: AutomodAction::Delete,
+        }
+    }
+}
+
+/// How member names are screened
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NameSettings
+{
+    /// Whether to reset names containing restricted words
+    pub filter: bool,
+
+    /// Whether to strip characters that sort names to the top of the member
+    /// list (e.g. `!`) from the start of names
+    pub dehoist: bool,
+
+    /// The nickname given in place of a bad name. `{id}` and
+    /// `{discriminator}` are replaced with the member's.
+    pub safe_nickname: String,
+}
+
+impl Default for NameSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            filter: false,
+            dehoist: false,
+            safe_nickname: "Moderated nickname {discriminator}".to_string(),
+        }
+    }
+}
+
+/// When a guild enters raid mode, and what happens to members who join
+/// during a raid
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RaidSettings
+{
+    /// Whether raid detection is on
+    pub enabled: bool,
+
+    /// The most members that may join within the window before raid mode
+    /// starts
+    pub max_joins: usize,
+
+    /// The length of the sliding window, in seconds
+    pub interval: u64,
+
+    /// Whether to kick members who join during a raid, instead of timing them
+    /// out
+    pub kick: bool,
+
+    /// How long to timeout members who join during a raid
+    pub timeout: crate::builtins::moderation::timeout::TimeoutTime,
+
+    /// How long raid mode lasts before ending by itself. Zero to last until
+    /// it's ended with `moderation raid end`.
+    pub lockdown: crate::builtins::moderation::timeout::TimeoutTime,
+}
+
+impl Default for RaidSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            enabled: false,
+            max_joins: 10,
+            interval: 60,
+            kick: false,
+            timeout: crate::builtins::moderation::timeout::TimeoutTime {
+                days: Some(1),
+                ..Default::default()
+            },
+            lockdown: crate::builtins::moderation::timeout::TimeoutTime {
+                minutes: Some(15),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Requirements new members must meet
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GateSettings
+{
+    /// How old an account must be to join. Zero for no minimum.
+    pub min_account_age: crate::builtins::moderation::timeout::TimeoutTime,
+
+    /// Whether accounts must have an avatar set
+    pub require_avatar: bool,
+
+    /// The role given to members who fail, instead of kicking them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quarantine_role: Option<RoleId>,
+}
+
+/// Which attachments may be posted
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AttachmentSettings
+{
+    /// File extensions that may not be posted, without the dot (e.g. `exe`)
+    pub denied_extensions: Vec<String>,
+
+    /// Content types that may not be posted. Entries ending in `/` or `/*`
+    /// deny every subtype (e.g. `video/*`).
+    pub denied_content_types: Vec<String>,
+
+    /// The largest attachment allowed, in kilobytes. Zero for no limit.
+    pub max_size: u64,
+
+    /// Rules for particular channels, on top of the guild's
+    pub channels: Vec<ChannelAttachments>,
+
+    /// What to do when an attachment is denied
+    pub action: AutomodAction,
+}
+
+impl AttachmentSettings
+{
+    /// A channel's rules, creating them if they don't exist
+    pub fn channel_mut(&mut self, channel: ChannelId) -> &mut ChannelAttachments
+    {
+        match self.channels.iter().position(|c| c.channel == channel) {
+            Some(i) => &mut self.channels[i],
+            None => {
+                self.channels.push(ChannelAttachments {
+                    channel,
+                    denied_extensions: Vec::new(),
+                    max_size: 0,
+                });
+                self.channels.last_mut().unwrap()
+            }
+        }
+    }
+}
+
+/// Attachment rules for one channel
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelAttachments
+{
+    pub channel: ChannelId,
+
+    /// File extensions that may not be posted in the channel
+    #[serde(default)]
+    pub denied_extensions: Vec<String>,
+
+    /// The largest attachment allowed in the channel, in kilobytes. Zero to
+    /// use the guild's limit.
+    #[serde(default)]
+    pub max_size: u64,
+}
+
+/// A step of the escalation ladder, taken when a member's active warning
+/// points reach its threshold
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscalationStep
+{
+    pub points: u32,
+    pub action: EscalationAction,
+}
+
+/// What an escalation step does
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EscalationAction
+{
+    Timeout(crate::builtins::moderation::timeout::TimeoutTime),
+    Kick,
+    Ban,
+}
+
+impl std::fmt::Display for EscalationAction
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            Self::Timeout(duration) => write!(f, "timeout for {duration}"),
+            Self::Kick => write!(f, "kick"),
+            Self::Ban => write!(f, "ban"),
+        }
+    }
+}
+
+impl From<WarnBehavior> for Vec<EscalationStep>
+{
+    /// A single warning behavior becomes a ladder of one step
+    fn from(behavior: WarnBehavior) -> Self
+    {
+        let (points, action) = match behavior {
+            WarnBehavior::Nothing => return Vec::new(),
+            WarnBehavior::Ban(cap) => (cap, EscalationAction::Ban),
+            WarnBehavior::Kick(cap) => (cap, EscalationAction::Kick),
+            WarnBehavior::Timeout {
+                warning_count,
+                duration,
+            } => (warning_count, EscalationAction::Timeout(duration)),
+        };
+        vec![EscalationStep {
+            points: points.max(1),
+            action,
+        }]
+    }
+}
+
+/// A named number of points for warnings
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WarningPreset
+{
+    pub name: String,
+    pub points: u32,
+}
+
+/// Which deleted and edited messages are posted to the log channel
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MessageLogSettings
+{
+    /// Whether deleted messages are logged
+    pub deletes: bool,
+
+    /// Whether edited messages are logged
+    pub edits: bool,
+
+    /// The only channels logged. Every channel is logged when empty.
+    pub watched_channels: Vec<ChannelId>,
+
+    /// Channels that are never logged
+    pub ignored_channels: Vec<ChannelId>,
+}
+
+impl Default for MessageLogSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            deletes: true,
+            edits: true,
+            watched_channels: Vec::new(),
+            ignored_channels: Vec::new(),
+        }
+    }
+}
+
+impl MessageLogSettings
+{
+    /// Whether messages in a channel are logged
+    pub fn watches(&self, channel: &ChannelId) -> bool
+    {
+        !self.ignored_channels.contains(channel)
+            && (self.watched_channels.is_empty() || self.watched_channels.contains(channel))
+    }
+}
+
+/// Which member events are posted to the log channel
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MemberLogSettings
+{
+    /// Members joining, with their account age
+    pub joins: bool,
+
+    /// Members leaving, with how long they were in the guild and their roles
+    pub leaves: bool,
+
+    /// Nickname changes
+    pub nicknames: bool,
+
+    /// Roles given or taken away
+    pub roles: bool,
+}
+
+impl Default for MemberLogSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            joins: true,
+            leaves: true,
+            nicknames: true,
+            roles: true,
+        }
+    }
+}
+
+/// A category of logged events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogCategory
+{
+    /// Every category
+    All,
+
+    /// Bans, kicks, warnings and timeouts
+    Moderation,
+
+    /// Automod hits, name screening, raids and join requirements
+    Automod,
+
+    /// Deleted and edited messages
+    Messages,
+
+    /// Members joining, leaving and changing
+    Members,
+}
+
+impl std::str::FromStr for LogCategory
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s.trim().to_lowercase().as_str() {
+            "all" => Ok(Self::All),
+            "moderation" => Ok(Self::Moderation),
+            "automod" => Ok(Self::Automod),
+            "messages" => Ok(Self::Messages),
+            "members" => Ok(Self::Members),
+            x => {
+                Err(format!(
+                    "Unknown log category '{x}'. The categories are all, moderation, automod, \
+                     messages and members."
+                ))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for LogCategory
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Moderation => write!(f, "moderation"),
+            Self::Automod => write!(f, "automod"),
+            Self::Messages => write!(f, "messages"),
+            Self::Members => write!(f, "members"),
+        }
+    }
+}
+
+/// The log channel of each category. Settings from before categories only
+/// have `log_channel`, which every category falls back to.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogChannels
+{
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<ChannelId>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub automod: Option<ChannelId>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<ChannelId>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub members: Option<ChannelId>,
+}
+
+impl LogChannels
+{
+    /// A category's own channel
+    pub fn get(&self, category: LogCategory) -> Option<ChannelId>
+    {
+        match category {
+            LogCategory::All => None,
+            LogCategory::Moderation => self.moderation,
+            LogCategory::Automod => self.automod,
+            LogCategory::Messages => self.messages,
+            LogCategory::Members => self.members,
+        }
+    }
+
+    /// A category's own channel, to change it. `All` has none.
+    pub fn get_mut(&mut self, category: LogCategory) -> Option<&mut Option<ChannelId>>
+    {
+        match category {
+            LogCategory::All => None,
+            LogCategory::Moderation => Some(&mut self.moderation),
+            LogCategory::Automod => Some(&mut self.automod),
+            LogCategory::Messages => Some(&mut self.messages),
+            LogCategory::Members => Some(&mut self.members),
+        }
+    }
+}
+
+/// A kind of automod rule that channels and roles can be exempt from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomodRule
+{
+    /// Every rule
+    All,
+
+    /// Restricted words
+    Words,
+
+    /// Link and invite filtering
+    Links,
+
+    /// Flood detection
+    Spam,
+
+    /// Mass mention protection
+    Mentions,
+
+    /// Attachment filtering
+    Attachments,
+}
+
+impl std::str::FromStr for AutomodRule
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s.trim().to_lowercase().as_str() {
+            "all" => Ok(Self::All),
+            "words" => Ok(Self::Words),
+            "links" => Ok(Self::Links),
+            "spam" => Ok(Self::Spam),
+            "mentions" => Ok(Self::Mentions),
+            "attachments" => Ok(Self::Attachments),
+            x => Err(format!("Unknown automod rule '{x}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for AutomodRule
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Words => write!(f, "words"),
+            Self::Links => write!(f, "links"),
+            Self::Spam => write!(f, "spam"),
+            Self::Mentions => write!(f, "mentions"),
+            Self::Attachments => write!(f, "attachments"),
+        }
+    }
+}
+
+/// Channels and roles exempt from one kind of automod rule
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Exemption
+{
+    pub channels: Vec<ChannelId>,
+    pub roles: Vec<RoleId>,
+}
+
+impl Exemption
+{
+    /// Whether a message in a channel, from a member with some roles, is exempt
+    pub fn covers(&self, channel: &ChannelId, roles: &[RoleId]) -> bool
+    {
+        self.channels.contains(channel) || roles.iter().any(|r| self.roles.contains(r))
+    }
+}
+
+/// Channels and roles exempt from each kind of automod rule
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Exemptions
+{
+    pub all: Exemption,
+    pub words: Exemption,
+    pub links: Exemption,
+    pub spam: Exemption,
+    pub mentions: Exemption,
+    pub attachments: Exemption,
+}
+
+impl Exemptions
+{
+    /// The exemptions for a kind of rule
+    pub fn get(&self, rule: AutomodRule) -> &Exemption
+    {
+        match rule {
+            AutomodRule::All => &self.all,
+            AutomodRule::Words => &self.words,
+            AutomodRule::Links => &self.links,
+            AutomodRule::Spam => &self.spam,
+            AutomodRule::Mentions => &self.mentions,
+            AutomodRule::Attachments => &self.attachments,
+        }
+    }
+
+    /// The exemptions for a kind of rule, mutably
+    pub fn get_mut(&mut self, rule: AutomodRule) -> &mut Exemption
+    {
+        match rule {
+            AutomodRule::All => &mut self.all,
+            AutomodRule::Words => &mut self.words,
+            AutomodRule::Links => &mut self.links,
+            AutomodRule::Spam => &mut self.spam,
+            AutomodRule::Mentions => &mut self.mentions,
+            AutomodRule::Attachments => &mut self.attachments,
+        }
+    }
+
+    /// Whether a message in a channel, from a member with some roles, is
+    /// exempt from a kind of rule
+    pub fn is_exempt(&self, rule: AutomodRule, channel: &ChannelId, roles: &[RoleId]) -> bool
+    {
+        self.all.covers(channel, roles) || self.get(rule).covers(channel, roles)
+    }
+
+    /// Whether any exemption depends on a member's roles
+    pub fn has_roles(&self) -> bool
+    {
+        [
+            &self.all,
+            &self.words,
+            &self.links,
+            &self.spam,
+            &self.mentions,
+            &self.attachments,
+        ]
+        .iter()
+        .any(|e| !e.roles.is_empty())
+    }
+}
+
 impl GuildSettings
 {
     /// Create a new, empty settings structure
@@ -93,6 +1133,24 @@ impl GuildSettings
         self
     }
 
+    /// A guild's settings, if it has any
+    pub fn get(&self, gid: &GuildId) -> Option<&Settings>
+    {
+        self.guilds
+            .iter()
+            .find(|g| g.gid == *gid)
+            .map(|g| &g.settings)
+    }
+
+    /// A guild's settings, if it has any, for modifying
+    pub fn get_mut(&mut self, gid: &GuildId) -> Option<&mut Settings>
+    {
+        self.guilds
+            .iter_mut()
+            .find(|g| g.gid == *gid)
+            .map(|g| &mut g.settings)
+    }
+
     /// If found, returns the location of the guild
     pub fn has_guild(&self, gid: &GuildId) -> (&Self, Option<usize>)
     {
@@ -120,7 +1178,7 @@ impl GuildSettings
     {
         let contents = read_to_string(&path)?;
 
-        let settings: GuildSettings = match toml::from_str(&contents) {
+        let mut settings: GuildSettings = match toml::from_str(&contents) {
             Ok(x) => x,
             Err(x) => {
                 println!("{contents}");
@@ -128,6 +1186,19 @@ impl GuildSettings
             }
         };
 
+        // Settings from before the escalation ladder have a single behavior
+        for guild in &mut settings.guilds {
+            let s = &mut guild.settings;
+            if s.escalation.is_empty() && !matches!(s.warning_behavior, WarnBehavior::Nothing) {
+                s.escalation = s.warning_behavior.into();
+                info!(
+                    "Converted the warning behavior of '{}' to an escalation ladder",
+                    guild.gid
+                );
+            }
+            s.warning_behavior = WarnBehavior::Nothing;
+        }
+
         info!("Loaded Guild Settings from '{}'", path.display());
         Ok(settings)
     }
@@ -166,8 +1237,16 @@ impl Settings
         self
     }
 
+    /// The channel a category of events is logged to
+    pub fn log_channel(&self, category: LogCategory) -> Option<ChannelId>
+    {
+        self.log_channels
+            .get(category)
+            .or_else(|| self.log_channel.as_ref().map(|c| c.id))
+    }
+
     /// Set the restricted words
-    pub fn _set_restricted_words(&mut self, words: Vec<String>) -> &mut Self
+    pub fn set_restricted_words(&mut self, words: Vec<RestrictedWord>) -> &mut Self
     {
         self.restricted_words = words;
         self
@@ -179,21 +1258,30 @@ impl Settings
         self
     }
 
-    /// Append a restricted word
-    pub fn _add_restricted_word(&mut self, word: String) -> &mut Self
+    /// Append a restricted word. Returns false if the pattern is already
+    /// restricted
+    pub fn add_restricted_word(&mut self, word: RestrictedWord) -> bool
     {
-        self.restricted_words.push(word);
-        self
+        if self
+            .restricted_words
+            .iter()
+            .any(|w| w.pattern() == word.pattern())
+        {
+            false
+        } else {
+            self.restricted_words.push(word);
+            true
+        }
     }
 
     /// Remove a restricted word
-    pub fn _remove_restricted_word(&mut self, word: String) -> Result<&mut Self, ()>
+    pub fn remove_restricted_word(&mut self, word: String) -> Result<&mut Self, ()>
     {
         if let Some(pos) = self
             .restricted_words
             .clone()
             .iter()
-            .position(|s| *s == word)
+            .position(|s| s.pattern() == word)
         {
             self.restricted_words.remove(pos);
             Ok(self)
@@ -202,12 +1290,52 @@ impl Settings
         }
     }
 
-    /// Set the warning behavior
+    /// Set the default action for restricted words
+    pub fn set_filter_action(&mut self, action: AutomodAction) -> &mut Self
+    {
+        self.filter_action = action;
+        self
+    }
+
+    /// Add a word to the allowlist. Returns false if it was already allowed
+    pub fn add_allowed_word(&mut self, word: String) -> bool
+    {
+        let word = word.to_lowercase();
+        if self.allowed_words.contains(&word) {
+            false
+        } else {
+            self.allowed_words.push(word);
+            true
+        }
+    }
+
+    /// Remove a word from the allowlist. Returns false if it wasn't allowed
+    pub fn remove_allowed_word(&mut self, word: &str) -> bool
+    {
+        let word = word.to_lowercase();
+        let len = self.allowed_words.len();
+        self.allowed_words.retain(|w| *w != word);
+        self.allowed_words.len() != len
+    }
+
+    /// Set the warning behavior, as a ladder of one step
     pub fn set_warning_behavior(&mut self, b: WarnBehavior) -> &mut Self
     {
-        self.warning_behavior = b;
+        self.escalation = b.into();
         self
     }
+
+    /// The highest escalation step crossed when a member's warning points rise
+    /// from `before` to `after`. Steps that were already reached don't fire
+    /// again.
+    pub fn escalation_step(&self, before: u32, after: u32) -> Option<EscalationStep>
+    {
+        self.escalation
+            .iter()
+            .filter(|s| before < s.points && s.points <= after)
+            .max_by_key(|s| s.points)
+            .copied()
+    }
 }
 
 strike! {
@@ -240,6 +1368,12 @@ strike! {
 
             #[serde(default = "_d_scratch" )]
             pub scratch: PathBuf,
+
+            #[serde(default = "_d_phishing_domains")]
+            pub phishing_domains: PathBuf,
+
+            #[serde(default = "_d_raids")]
+            pub raids: PathBuf,
         },
 
     }
@@ -250,6 +1384,8 @@ fn _d_warnings() -> PathBuf { PathBuf::from("/var/local/bor/warnings.toml") }
 fn _d_guild_settings() -> PathBuf { PathBuf::from("/var/local/bor/guild_settings.toml") }
 fn _d_analytics() -> PathBuf { PathBuf::from("/var/local/bor/analytics.data") }
 fn _d_scratch() -> PathBuf { PathBuf::from("/tmp/bor/") }
+fn _d_phishing_domains() -> PathBuf { PathBuf::from("/etc/bor/phishing_domains.txt") }
+fn _d_raids() -> PathBuf { PathBuf::from("/var/local/bor/raids.toml") }
 
 impl Config
 {
@@ -289,3 +1425,34 @@ impl Config
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::builtins::moderation::timeout::TimeoutTime;
+
+    #[test]
+    fn test_escalation_step()
+    {
+        let step = |points, action| EscalationStep { points, action };
+        let settings = Settings {
+            escalation: vec![
+                step(3, EscalationAction::Kick),
+                step(1, EscalationAction::Timeout(TimeoutTime::default())),
+                step(5, EscalationAction::Ban),
+            ],
+            ..Default::default()
+        };
+        let points = |before, after| settings.escalation_step(before, after).map(|s| s.points);
+
+        assert_eq!(points(0, 1), Some(1));
+        assert_eq!(points(0, 4), Some(3));
+        assert_eq!(points(2, 10), Some(5));
+        assert_eq!(points(3, 4), None);
+        assert_eq!(points(4, 5), Some(5));
+        assert_eq!(points(5, 5), None);
+        assert_eq!(points(4, 2), None);
+        assert_eq!(Settings::default().escalation_step(0, 10), None);
+    }
+}
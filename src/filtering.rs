@@ -1 +1,634 @@
-pub fn _is_restricted(word: String, r: &[String]) -> bool { r.contains(&word) }
+//! Restricted word matching
+//!
+//! Message content is folded before matching so that common evasions still
+//! match: Unicode compatibility forms (fullwidth, mathematical letters) and
+//! diacritics are folded, invisible characters are dropped, lookalike letters
+//! from other scripts are mapped to their Latin skeleton, separators inside
+//! words are stripped and leetspeak is substituted.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use log::error;
+use regex::{Regex, RegexBuilder};
+use serenity::model::prelude::GuildId;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::config::{AutomodAction, MatchMode, RestrictedWord, Settings};
+
+lazy_static! {
+    /// Each guild's restricted words, compiled when the guild's lists change
+    static ref GUILDS: Mutex<HashMap<GuildId, (Settings, Arc<Matcher>)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The compiled restricted words of a guild, merged with the global restricted
+/// words. The lists are only recompiled when the guild's lists differ from the
+/// ones last compiled for it.
+pub fn guild_matcher(gid: &GuildId, settings: &Settings) -> Arc<Matcher>
+{
+    let mut guilds = GUILDS.lock().unwrap();
+    if let Some((compiled, matcher)) = guilds.get(gid) {
+        if compiled.restricted_words == settings.restricted_words
+            && compiled.allowed_words == settings.allowed_words
+            && compiled.filter_action == settings.filter_action
+        {
+            return matcher.clone();
+        }
+    }
+
+    // Each entry carries its own action, or its list's action
+    let live = crate::reload::get();
+    let global = &live.restricted_words;
+    let global_action = global.action.unwrap_or_default();
+    let words: Vec<RestrictedWord> = global
+        .words
+        .iter()
+        .map(|w| w.clone().or_action(global_action))
+        .chain(
+            settings
+                .restricted_words
+                .iter()
+                .map(|w| w.clone().or_action(settings.filter_action)),
+        )
+        .collect();
+    let allowed: Vec<String> = global
+        .allowed
+        .iter()
+        .chain(settings.allowed_words.iter())
+        .cloned()
+        .collect();
+
+    let matcher = Arc::new(Matcher::new(&words, &allowed));
+    guilds.insert(*gid, (settings.clone(), matcher.clone()));
+    matcher
+}
+
+/// Forget every compiled list, so they're recompiled with the current global
+/// restricted words
+pub fn clear() { GUILDS.lock().unwrap().clear() }
+
+/// A restricted word found in a message
+#[derive(Debug, Clone)]
+pub struct Match
+{
+    /// The entry that matched
+    pub rule: RestrictedWord,
+
+    /// The normalized text the entry matched
+    pub matched: String,
+}
+
+impl Match
+{
+    /// What to do about the match
+    pub fn action(&self) -> AutomodAction { self.rule.action().unwrap_or_default() }
+}
+
+enum Kind
+{
+    /// Equal to a whole word
+    Word(Forms),
+
+    /// Contained in a word
+    Substring(Forms),
+
+    /// A regex matching a whole word
+    Token(Regex),
+
+    /// A regex matching anywhere in the message
+    Regex(Regex),
+}
+
+struct Rule
+{
+    source: RestrictedWord,
+    kind: Kind,
+}
+
+/// A list of restricted words compiled for matching
+pub struct Matcher
+{
+    rules: Vec<Rule>,
+
+    /// Words that are never reported
+    allowed: Vec<Forms>,
+}
+
+impl Matcher
+{
+    /// Compile a list of restricted words and the allowlist consulted before
+    /// reporting a match. Entries that fail to compile are logged and skipped.
+    pub fn new(words: &[RestrictedWord], allowed: &[String]) -> Self
+    {
+        let rules = words
+            .iter()
+            .filter_map(|w| {
+                match compile(w) {
+                    Ok(kind) => {
+                        Some(Rule {
+                            source: w.clone(),
+                            kind,
+                        })
+                    }
+                    Err(e) => {
+                        error!("Skipping restricted word '{}': {e}", w.pattern());
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let allowed = allowed
+            .iter()
+            .map(|w| Forms::new(w))
+            .filter(|f| !f.plain.is_empty())
+            .collect();
+
+        Self { rules, allowed }
+    }
+
+    /// Find the first restricted word in `content`
+    pub fn find(&self, content: &str) -> Option<Match>
+    {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        let text = Text::new(content);
+        self.rules
+            .iter()
+            .find_map(|rule| self.match_rule(rule, &text))
+    }
+
+    /// Find every restricted word in `content`, in the order they're checked
+    pub fn find_all(&self, content: &str) -> Vec<Match>
+    {
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+
+        let text = Text::new(content);
+        self.rules
+            .iter()
+            .filter_map(|rule| self.match_rule(rule, &text))
+            .collect()
+    }
+
+    fn match_rule(&self, rule: &Rule, text: &Text) -> Option<Match>
+    {
+        self.find_rule(rule, text).map(|matched| {
+            Match {
+                rule: rule.source.clone(),
+                matched,
+            }
+        })
+    }
+
+    fn find_rule(&self, rule: &Rule, text: &Text) -> Option<String>
+    {
+        let mut tokens = text.tokens.iter().filter(|t| !self.is_allowed(t));
+        match &rule.kind {
+            Kind::Word(p) => {
+                tokens
+                    .find(|t| (!p.plain.is_empty() && t.plain == p.plain) || t.leet == p.leet)
+                    .map(|t| t.plain.clone())
+            }
+            Kind::Substring(p) => {
+                tokens
+                    .find(|t| {
+                        (!p.plain.is_empty() && t.plain.contains(&p.plain))
+                            || t.leet.contains(&p.leet)
+                    })
+                    .map(|t| t.plain.clone())
+            }
+            Kind::Token(re) => {
+                tokens
+                    .find(|t| re.is_match(&t.plain) || re.is_match(&t.leet))
+                    .map(|t| t.plain.clone())
+            }
+            Kind::Regex(re) => {
+                [text.plain.as_str(), text.leet.as_str()]
+                    .into_iter()
+                    .find_map(|s| {
+                        re.find_iter(s)
+                            .find(|m| {
+                                !self.is_allowed(&Forms::new(surrounding(s, m.start(), m.end())))
+                            })
+                            .map(|m| m.as_str().to_string())
+                    })
+            }
+        }
+    }
+
+    fn is_allowed(&self, word: &Forms) -> bool
+    {
+        self.allowed
+            .iter()
+            .any(|a| a.plain == word.plain || a.leet == word.leet)
+    }
+}
+
+/// Check that a restricted word compiles
+pub fn validate(word: &RestrictedWord) -> Result<(), String> { compile(word).map(|_| ()) }
+
+fn compile(word: &RestrictedWord) -> Result<Kind, String>
+{
+    let pattern = word.pattern();
+    if pattern.trim().is_empty() {
+        return Err("Empty pattern".to_string());
+    }
+
+    let forms = Forms::new(pattern);
+    let word_mode = matches!(word.mode(), MatchMode::Word | MatchMode::Substring);
+    if word_mode && forms.leet.is_empty() {
+        return Err("Pattern has no letters or numbers".to_string());
+    }
+
+    Ok(match word.mode() {
+        MatchMode::Word => Kind::Word(forms),
+        MatchMode::Substring if word.whole_word() => Kind::Word(forms),
+        MatchMode::Substring => Kind::Substring(forms),
+        MatchMode::Glob => {
+            let re = glob_to_regex(&fold(pattern));
+            Kind::Token(Regex::new(&re).map_err(|e| e.to_string())?)
+        }
+        MatchMode::Regex => {
+            let pattern = if word.whole_word() {
+                format!("^(?:{pattern})$")
+            } else {
+                pattern.to_string()
+            };
+            let re = RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .size_limit(1 << 20)
+                .build()
+                .map_err(|e| e.to_string())?;
+
+            if word.whole_word() {
+                Kind::Token(re)
+            } else {
+                Kind::Regex(re)
+            }
+        }
+    })
+}
+
+/// The whitespace delimited words of `text` surrounding `start..end`
+fn surrounding(text: &str, start: usize, end: usize) -> &str
+{
+    let start = text[..start]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let end = text[end..]
+        .find(char::is_whitespace)
+        .map(|i| end + i)
+        .unwrap_or(text.len());
+
+    &text[start..end]
+}
+
+/// The plain and leetspeak forms of a word, with separators stripped
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Forms
+{
+    plain: String,
+    leet: String,
+}
+
+impl Forms
+{
+    fn new(word: &str) -> Self
+    {
+        let folded = fold(word);
+        Self {
+            plain: folded.chars().filter(|c| c.is_alphanumeric()).collect(),
+            leet: leetspeak(&folded)
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect(),
+        }
+    }
+}
+
+/// Message content prepared for matching
+struct Text
+{
+    /// The folded message
+    plain: String,
+
+    /// The folded message with leetspeak substituted
+    leet: String,
+
+    /// The words of the message, plus runs of single characters joined
+    /// together (e.g. `b a d`)
+    tokens: Vec<Forms>,
+}
+
+impl Text
+{
+    fn new(content: &str) -> Self
+    {
+        let plain = fold(content);
+        let leet = leetspeak(&plain);
+
+        let mut tokens: Vec<Forms> = Vec::new();
+        let mut run: Vec<Forms> = Vec::new();
+        for word in plain.split_whitespace() {
+            let forms = Forms::new(word);
+            if forms.plain.is_empty() && forms.leet.is_empty() {
+                continue;
+            }
+
+            if forms.plain.chars().count() <= 1 {
+                run.push(forms.clone());
+            } else {
+                push_run(&mut tokens, &mut run);
+            }
+            tokens.push(forms);
+        }
+        push_run(&mut tokens, &mut run);
+
+        Self {
+            plain,
+            leet,
+            tokens,
+        }
+    }
+}
+
+/// Join a run of single character words into one token
+fn push_run(tokens: &mut Vec<Forms>, run: &mut Vec<Forms>)
+{
+    if run.len() > 1 {
+        tokens.push(Forms {
+            plain: run.iter().map(|f| f.plain.as_str()).collect(),
+            leet: run.iter().map(|f| f.leet.as_str()).collect(),
+        });
+    }
+    run.clear();
+}
+
+/// Normalize text for matching: compatibility folding, diacritic and
+/// invisible character removal, lowercasing and confusable mapping
+pub fn fold(text: &str) -> String
+{
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c) && !is_invisible(*c))
+        .flat_map(char::to_lowercase)
+        .map(skeleton)
+        .collect()
+}
+
+/// The forms of some text that restricted words are matched against: the
+/// folded text, and the folded text with leetspeak substituted
+pub fn normalize(text: &str) -> (String, String)
+{
+    let text = Text::new(text);
+    (text.plain, text.leet)
+}
+
+/// Convert a glob (`*`, `?`) into a regex matching a whole word
+fn glob_to_regex(glob: &str) -> String
+{
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// Zero-width and other characters that render as nothing
+fn is_invisible(c: char) -> bool
+{
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{061C}'
+            | '\u{115F}'
+            | '\u{1160}'
+            | '\u{17B4}'
+            | '\u{17B5}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{206F}'
+            | '\u{3164}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FEFF}'
+            | '\u{FFA0}'
+    )
+}
+
+/// Map lowercase lookalike letters from other scripts to Latin
+fn skeleton(c: char) -> char
+{
+    match c {
+        // Cyrillic
+        'а' => 'a',
+        'в' => 'b',
+        'е' | 'ё' => 'e',
+        'һ' | 'н' => 'h',
+        'і' | 'ї' => 'i',
+        'ј' => 'j',
+        'к' => 'k',
+        'ӏ' => 'l',
+        'м' => 'm',
+        'о' => 'o',
+        'р' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'т' => 't',
+        'с' => 'c',
+        'ԁ' => 'd',
+        'у' => 'y',
+        'ԝ' => 'w',
+        'х' => 'x',
+        // Greek
+        'α' => 'a',
+        'β' => 'b',
+        'ε' => 'e',
+        'η' => 'n',
+        'ι' => 'i',
+        'κ' => 'k',
+        'ν' => 'v',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'τ' => 't',
+        'υ' => 'u',
+        'χ' => 'x',
+        'ω' => 'w',
+        // Latin variants
+        'ı' | 'ɩ' => 'i',
+        'ɡ' => 'g',
+        'ℓ' => 'l',
+        c => c,
+    }
+}
+
+/// Substitute leetspeak in some text. `!` is usually punctuation, so it's only
+/// read as `i` between letters (e.g. `sh!t`, but not `hi!`).
+fn leetspeak(text: &str) -> String
+{
+    let chars: Vec<char> = text.chars().collect();
+    let is_letter =
+        |i: Option<usize>| matches!(i.and_then(|i| chars.get(i)), Some(c) if c.is_alphabetic());
+
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            match c {
+                '!' if is_letter(i.checked_sub(1)) && is_letter(Some(i + 1)) => 'i',
+                c => leet(c),
+            }
+        })
+        .collect()
+}
+
+/// Substitute a leetspeak character
+fn leet(c: char) -> char
+{
+    match c {
+        '0' => 'o',
+        '1' => 'i',
+        '3' | '€' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' | '+' => 't',
+        '8' => 'b',
+        '9' => 'g',
+        '|' => 'l',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn matcher(words: &[&str]) -> Matcher
+    {
+        Matcher::new(
+            &words
+                .iter()
+                .map(|w| RestrictedWord::Word(w.to_string()))
+                .collect::<Vec<_>>(),
+            &[],
+        )
+    }
+
+    fn rule(pattern: &str, mode: MatchMode) -> Matcher
+    {
+        Matcher::new(
+            &[RestrictedWord::Rule {
+                pattern: pattern.to_string(),
+                mode,
+                whole_word: false,
+                action: None,
+            }],
+            &[],
+        )
+    }
+
+    #[test]
+    fn test_word_evasions()
+    {
+        let m = matcher(&["bad"]);
+        assert!(m.find("this is bad!").is_some());
+        assert!(m.find("b.a.d").is_some());
+        assert!(m.find("b4d").is_some());
+        assert!(m.find("b\u{200B}ad").is_some());
+        assert!(m.find("bаd").is_some()); // Cyrillic 'а'
+        assert!(m.find("ｂａｄ").is_some());
+        assert!(m.find("b a d").is_some());
+        assert!(m.find("BÁD").is_some());
+        assert!(m.find("badger").is_none());
+        assert!(m.find("a b").is_none());
+    }
+
+    #[test]
+    fn test_exclamation()
+    {
+        let m = matcher(&["shit", "hii"]);
+        assert!(m.find("oh sh!t").is_some());
+        assert!(m.find("hi!").is_none());
+        assert!(m.find("well hi! there").is_none());
+        assert_eq!(normalize("sh!t hi! !hi").1, "shit hi! !hi");
+    }
+
+    #[test]
+    fn test_modes()
+    {
+        assert!(rule("bad", MatchMode::Substring).find("badger").is_some());
+        assert!(rule("b*d", MatchMode::Glob).find("the bird").is_some());
+        assert!(rule("b*d", MatchMode::Glob).find("birds").is_none());
+        assert!(rule(r"fo+bar", MatchMode::Regex).find("FOOOBAR").is_some());
+        assert!(validate(&RestrictedWord::Rule {
+            pattern: "(".to_string(),
+            mode: MatchMode::Regex,
+            whole_word: false,
+            action: None,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_allowlist()
+    {
+        let words = [
+            RestrictedWord::Rule {
+                pattern: "ass".to_string(),
+                mode: MatchMode::Substring,
+                whole_word: false,
+                action: None,
+            },
+            RestrictedWord::Rule {
+                pattern: "cla+".to_string(),
+                mode: MatchMode::Regex,
+                whole_word: false,
+                action: None,
+            },
+        ];
+        let m = Matcher::new(&words, &["classic".to_string(), "assessment".to_string()]);
+        assert!(m.find("a classic assessment").is_none());
+        assert!(m.find("a cl4ssic").is_none());
+        assert!(m.find("you ass").is_some());
+        assert!(m.find("claaap").is_some());
+
+        let whole = Matcher::new(
+            &[RestrictedWord::Rule {
+                pattern: "ass".to_string(),
+                mode: MatchMode::Substring,
+                whole_word: true,
+                action: None,
+            }],
+            &[],
+        );
+        assert!(whole.find("grass").is_none());
+        assert!(whole.find("a$$").is_some());
+    }
+
+    #[test]
+    fn test_find_all()
+    {
+        let m = matcher(&["bad", "worse", "worst"]);
+        let found = m.find_all("b4d and w0rse");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].matched, "b4d");
+        assert_eq!(found[1].matched, "w0rse");
+    }
+}
@@ -0,0 +1,177 @@
+//! Logging members joining, leaving and changing
+//!
+//! Members are cached as they join or change, and when the bot first sees a
+//! guild. Leaves and changes of members who aren't cached are logged with
+//! what's known.
+
+use serenity::{
+    model::{
+        event::GuildMemberUpdateEvent,
+        prelude::{GuildId, Member, RoleId, User},
+        timestamp::Timestamp,
+    },
+    prelude::Context,
+    utils::Colour,
+};
+
+use super::log;
+use crate::{
+    builtins::settings,
+    cache::{self, CachedMember},
+    config::LogCategory,
+};
+
+/// Log a member joining
+pub async fn joined(context: &Context, member: &Member)
+{
+    let gid = member.guild_id;
+    let user = &member.user;
+    cache::insert_member(gid, user.id, member.into());
+
+    if !settings::get(&gid).member_log.joins {
+        return;
+    }
+
+    let description = format!(
+        "{} (<@{}>) joined.\n**Account made:** {}",
+        user.tag(),
+        user.id,
+        since(user.id.created_at())
+    );
+    log(
+        context,
+        &gid,
+        LogCategory::Members,
+        "Member joined",
+        description,
+        Colour::DARK_GREEN,
+    )
+    .await;
+}
+
+/// Log a member leaving, whether they left, were kicked or were banned
+pub async fn left(context: &Context, gid: &GuildId, user: &User)
+{
+    let member = cache::remove_member(*gid, user.id);
+
+    if !settings::get(gid).member_log.leaves {
+        return;
+    }
+
+    let mut description = format!("{} (<@{}>) left.", user.tag(), user.id);
+    match member {
+        Some(member) => {
+            if let Some(joined) = member.joined_at {
+                description.push_str(&format!("\n**Joined:** {}", since(joined)));
+            }
+            description.push_str(&format!("\n**Roles:** {}", roles(&member.roles)));
+        }
+        None => description.push_str("\nWhen they joined and their roles aren't known."),
+    }
+    log(
+        context,
+        gid,
+        LogCategory::Members,
+        "Member left",
+        description,
+        Colour::ORANGE,
+    )
+    .await;
+}
+
+/// Log a member's nickname and role changes
+pub async fn updated(context: &Context, event: &GuildMemberUpdateEvent)
+{
+    let gid = event.guild_id;
+    let user = &event.user;
+    let after = CachedMember {
+        joined_at: Some(event.joined_at),
+        nick: event.nick.clone(),
+        roles: event.roles.clone(),
+    };
+    // Without the member as they were, there's nothing to compare against
+    let before = match cache::insert_member(gid, user.id, after.clone()) {
+        Some(x) => x,
+        None => return,
+    };
+
+    let member_log = settings::get(&gid).member_log;
+
+    if member_log.nicknames && before.nick != after.nick {
+        let name = |nick: &Option<String>| {
+            match nick {
+                Some(n) => n.clone(),
+                None => "*None*".to_string(),
+            }
+        };
+        let description = format!(
+            "{} (<@{}>) changed their nickname.\n**Before:** {}\n**After:** {}",
+            user.tag(),
+            user.id,
+            name(&before.nick),
+            name(&after.nick)
+        );
+        log(
+            context,
+            &gid,
+            LogCategory::Members,
+            "Nickname changed",
+            description,
+            Colour::BLUE,
+        )
+        .await;
+    }
+
+    let added: Vec<RoleId> = after
+        .roles
+        .iter()
+        .filter(|r| !before.roles.contains(r))
+        .copied()
+        .collect();
+    let removed: Vec<RoleId> = before
+        .roles
+        .iter()
+        .filter(|r| !after.roles.contains(r))
+        .copied()
+        .collect();
+
+    if member_log.roles && (!added.is_empty() || !removed.is_empty()) {
+        let mut description = format!("{} (<@{}>)'s roles changed.", user.tag(), user.id);
+        if !added.is_empty() {
+            description.push_str(&format!("\n**Given:** {}", roles(&added)));
+        }
+        if !removed.is_empty() {
+            description.push_str(&format!("\n**Taken away:** {}", roles(&removed)));
+        }
+        log(
+            context,
+            &gid,
+            LogCategory::Members,
+            "Roles changed",
+            description,
+            Colour::BLUE,
+        )
+        .await;
+    }
+}
+
+/// When something happened, and how many days ago
+fn since(when: Timestamp) -> String
+{
+    let days = (Timestamp::now().unix_timestamp() - when.unix_timestamp()) / 86_400;
+    format!("<t:{}:F> ({days} days ago)", when.unix_timestamp())
+}
+
+/// Role mentions, which don't ping in embeds
+fn roles(roles: &[RoleId]) -> String
+{
+    if roles.is_empty() {
+        "none".to_string()
+    } else {
+        roles
+            .iter()
+            .map(|r| format!("<@&{r}>"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
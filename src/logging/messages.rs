@@ -0,0 +1,156 @@
+//! Logging deleted and edited messages
+//!
+//! Only messages in the cache can be shown. Deleted messages that aren't
+//! cached aren't logged: bot messages, messages sent before the bot started or
+//! pushed out of the cache, and messages automod removed, which it logs
+//! itself. Edits of messages that aren't cached are logged without their old
+//! content.
+
+use serenity::{
+    model::prelude::{ChannelId, GuildId, Message, MessageId},
+    prelude::Context,
+    utils::Colour,
+};
+
+use super::{log, shorten, MAX_CONTENT};
+use crate::{
+    builtins::settings,
+    cache::{self, CachedMessage},
+    config::LogCategory,
+};
+
+/// The most characters shown for all the messages of a bulk delete
+const MAX_BULK: usize = 3_500;
+
+/// Log a deleted message
+pub async fn deleted(context: &Context, gid: &GuildId, channel: &ChannelId, id: &MessageId)
+{
+    // There's nothing to show for messages that aren't cached. Messages
+    // automod removes are taken out of the cache first, so they aren't logged
+    // twice.
+    let msg = match cache::remove(id) {
+        Some(x) => x,
+        None => return,
+    };
+
+    let log_settings = settings::get(gid).message_log;
+    if !log_settings.deletes || !log_settings.watches(channel) {
+        return;
+    }
+
+    let description = format!(
+        "**Author:** {} (<@{}>)\n**Channel:** <#{channel}>\n**Content:**\n{}",
+        msg.author.tag(),
+        msg.author.id,
+        describe(&msg.content, &msg.attachments, MAX_CONTENT)
+    );
+    log(
+        context,
+        gid,
+        LogCategory::Messages,
+        "Message deleted",
+        description,
+        Colour::RED,
+    )
+    .await;
+}
+
+/// Log messages deleted at once, such as by a purge
+pub async fn bulk_deleted(context: &Context, gid: &GuildId, channel: &ChannelId, ids: &[MessageId])
+{
+    let messages: Vec<CachedMessage> = ids.iter().filter_map(cache::remove).collect();
+
+    let log_settings = settings::get(gid).message_log;
+    if !log_settings.deletes || !log_settings.watches(channel) {
+        return;
+    }
+
+    let mut description = format!(
+        "{} messages were deleted in <#{channel}>. {} of them are known.",
+        ids.len(),
+        messages.len()
+    );
+    let mut remaining = MAX_BULK;
+    for msg in &messages {
+        let line = format!(
+            "\n**{}:** {}",
+            msg.author.tag(),
+            describe(&msg.content, &msg.attachments, 200)
+        );
+        let length = line.chars().count();
+        if length > remaining {
+            description.push_str("\n...");
+            break;
+        }
+        remaining -= length;
+        description.push_str(&line);
+    }
+
+    log(
+        context,
+        gid,
+        LogCategory::Messages,
+        "Messages deleted",
+        description,
+        Colour::RED,
+    )
+    .await;
+}
+
+/// Log an edited message. `before` is the message before the edit, if it
+/// was cached.
+pub async fn edited(
+    context: &Context,
+    gid: &GuildId,
+    before: Option<CachedMessage>,
+    after: &Message,
+)
+{
+    let log_settings = settings::get(gid).message_log;
+    if !log_settings.edits || !log_settings.watches(&after.channel_id) {
+        return;
+    }
+
+    let attachments: Vec<String> = after
+        .attachments
+        .iter()
+        .map(|a| a.filename.clone())
+        .collect();
+    let before = match before {
+        Some(msg) => describe(&msg.content, &msg.attachments, MAX_CONTENT),
+        None => "*Unknown, the message wasn't cached*".to_string(),
+    };
+
+    let description = format!(
+        "**Author:** {} (<@{}>)\n**Channel:** <#{}> \
+         ([jump]({}))\n**Before:**\n{before}\n**After:**\n{}",
+        after.author.tag(),
+        after.author.id,
+        after.channel_id,
+        after.link(),
+        describe(&after.content, &attachments, MAX_CONTENT)
+    );
+    log(
+        context,
+        gid,
+        LogCategory::Messages,
+        "Message edited",
+        description,
+        Colour::GOLD,
+    )
+    .await;
+}
+
+/// Describe a message's content and attachments
+fn describe(content: &str, attachments: &[String], max: usize) -> String
+{
+    let mut s = if content.is_empty() {
+        "*No text*".to_string()
+    } else {
+        shorten(content, max)
+    };
+    if !attachments.is_empty() {
+        s.push_str(&format!(" (attachments: {})", attachments.join(", ")));
+    }
+    s
+}
@@ -0,0 +1,60 @@
+//! Post bot events to a guild's configured log channel
+
+use log::error;
+use serenity::{
+    model::{prelude::GuildId, timestamp::Timestamp},
+    prelude::Context,
+    utils::Colour,
+};
+
+use crate::{builtins::settings, config::LogCategory};
+
+pub mod members;
+pub mod messages;
+
+/// The most characters of an embed's description. Discord allows 4096.
+const MAX_DESCRIPTION: usize = 4_000;
+
+/// The most characters of a message's content shown in the log
+pub const MAX_CONTENT: usize = 1_500;
+
+/// Post an embed to the guild's log channel for a category of events. Does
+/// nothing when the guild has no log channel for it.
+pub async fn log(
+    context: &Context,
+    gid: &GuildId,
+    category: LogCategory,
+    title: &str,
+    description: String,
+    colour: Colour,
+)
+{
+    let channel = match settings::get(gid).log_channel(category) {
+        Some(c) => c,
+        None => return,
+    };
+
+    if let Err(why) = channel
+        .send_message(&context.http, |m| {
+            m.embed(|e| {
+                e.title(title)
+                    .description(shorten(&description, MAX_DESCRIPTION))
+                    .colour(colour)
+                    .timestamp(Timestamp::now())
+            })
+        })
+        .await
+    {
+        error!("Error posting to log channel: {:?}", why);
+    }
+}
+
+/// Shorten text to fit in a log embed
+pub fn shorten(text: &str, max: usize) -> String
+{
+    if text.chars().count() > max {
+        format!("{}...", text.chars().take(max).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
@@ -1,16 +1,20 @@
+mod automod;
 mod builtins;
+mod cache;
 mod commands;
 mod config;
 mod data;
 mod filtering;
+mod logging;
+mod reload;
+mod warnings;
 
 extern crate bor_conversions as conversions;
 extern crate bor_define as define;
-extern crate bor_warn as warn;
 extern crate bor_wiki as wiki;
 
 use chrono::{Duration, Utc};
-use config::{Config, RestrictedWords};
+use config::Config;
 use lazy_static::lazy_static;
 use log::{error, info};
 use serenity::{
@@ -39,8 +43,6 @@ lazy_static! {
         ))
     };
     static ref CONFIG: config::Config = Config::from(config_file()).unwrap();
-    static ref RESTRICTED_WORDS: config::RestrictedWords =
-        RestrictedWords::from(CONFIG.resources.restricted_words.clone()).unwrap();
     pub static ref DATA: Mutex<data::UsageData> = Mutex::new({
         match data::UsageData::load(CONFIG.resources.analytics.clone()) {
             Some(x) => x,
@@ -91,6 +93,20 @@ enum InfoSubOptions
 
     /// List the guilds that have used commands in the past month
     ListGuilds,
+
+    /// Add a word to the global restricted word allowlist
+    AllowWord
+    {
+        /// The word to allow
+        word: String,
+    },
+
+    /// Remove a word from the global restricted word allowlist
+    DisallowWord
+    {
+        /// The word to stop allowing
+        word: String,
+    },
 }
 
 #[tokio::main]
@@ -119,6 +135,33 @@ async fn main() -> Result<(), std::io::Error>
                         }
                     };
                 }
+
+                InfoSubOptions::AllowWord { word } => {
+                    let path = CONFIG.resources.restricted_words.clone();
+                    match config::RestrictedWords::allow(path, word) {
+                        Err(e) => eprintln!("Couldn't save restricted words: {e}"),
+                        Ok(false) => println!("'{word}' is already allowed."),
+                        Ok(true) => {
+                            println!(
+                                "Allowed '{word}'. Reload any bots that use this list with SIGHUP."
+                            )
+                        }
+                    }
+                }
+
+                InfoSubOptions::DisallowWord { word } => {
+                    let path = CONFIG.resources.restricted_words.clone();
+                    match config::RestrictedWords::disallow(path, word) {
+                        Err(e) => eprintln!("Couldn't save restricted words: {e}"),
+                        Ok(false) => println!("'{word}' is not on the allowlist."),
+                        Ok(true) => {
+                            println!(
+                                "Removed '{word}' from the allowlist. Reload any bots that use \
+                                 this list with SIGHUP."
+                            )
+                        }
+                    }
+                }
             }
 
             // Stop execution here
@@ -129,6 +172,23 @@ async fn main() -> Result<(), std::io::Error>
 
     env_logger::init();
     info!("Initialized Logger");
+    info!(
+        "Loaded {} restricted words",
+        reload::get().restricted_words.words.len()
+    );
+
+    // Reload the restricted words and config on SIGHUP
+    #[cfg(unix)]
+    spawn(async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = signal(SignalKind::hangup()).expect("Unable to listen for SIGHUP");
+        while hangup.recv().await.is_some() {
+            if let Err(e) = reload::reload() {
+                error!("{e}. Keeping the current configuration.");
+            }
+        }
+    });
 
     let data_management = tokio_schedule::every(1)
         .day()
@@ -139,7 +199,8 @@ async fn main() -> Result<(), std::io::Error>
     let token = &CONFIG.secrets.token;
     let intents = GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT;
+        | GatewayIntents::MESSAGE_CONTENT
+        | GatewayIntents::GUILD_MEMBERS;
 
     let mut client = Client::builder(token, intents)
         .event_handler(Handler)
@@ -242,26 +303,135 @@ impl EventHandler for Handler
         }
     }
 
-    // async fn message(&self, context: Context, msg: Message)
-    // {
-    //     // Check for restricted words and remove them
-    //     if !msg.is_private()
-    //         && filtering::is_restricted(msg.content.clone(),
-    // &RESTRICTED_WORDS.words)     {
-    //         if let Err(why) = msg.delete(&context.http).await
-    //         {
-    //             error!("Error removing message: {:?}", why);
-    //         }
-    //         let response = MessageBuilder::new()
-    //             .mention(&msg.author)
-    //             .push("Used a restricted word!")
-    //             .build();
-    //         if let Err(why) = msg.channel_id.say(&context.http, &response).await
-    //         {
-    //             error!("Error sending message: {:?}", why);
-    //         }
-    //     }
-    // }
+    async fn message(&self, context: Context, msg: Message)
+    {
+        // Check guild messages for restricted words and remove them
+        if !msg.author.bot && !msg.is_private() {
+            cache::insert(&msg);
+            automod::check_message(&context, &msg).await;
+        }
+    }
+
+    async fn message_update(&self, context: Context, event: MessageUpdateEvent)
+    {
+        // Loading a link's embed also counts as an update, so only look at
+        // guild messages whose content changed
+        let (gid, content) = match (event.guild_id, &event.content) {
+            (Some(gid), Some(content)) => (gid, content),
+            _ => return,
+        };
+        if event.author.as_ref().map_or(false, |a| a.bot) {
+            return;
+        }
+
+        let before = cache::update(&event.id, content);
+        if before.as_ref().map(|m| &m.content) == Some(content) {
+            return;
+        }
+
+        // Re-check edited messages for restricted words and remove them
+        let mut msg = match event.channel_id.message(&context.http, event.id).await {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Error fetching edited message '{}': {:?}", event.id, e);
+                return;
+            }
+        };
+        msg.guild_id = Some(gid);
+        cache::insert(&msg);
+        if !msg.author.bot {
+            logging::messages::edited(&context, &gid, before.clone(), &msg).await;
+            automod::check_edit(&context, &msg, before.map(|m| m.content)).await;
+        }
+    }
+
+    async fn message_delete(
+        &self,
+        context: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        guild_id: Option<GuildId>,
+    )
+    {
+        if let Some(gid) = guild_id {
+            logging::messages::deleted(&context, &gid, &channel_id, &deleted_message_id).await;
+        }
+    }
+
+    async fn message_delete_bulk(
+        &self,
+        context: Context,
+        channel_id: ChannelId,
+        multiple_deleted_messages_ids: Vec<MessageId>,
+        guild_id: Option<GuildId>,
+    )
+    {
+        if let Some(gid) = guild_id {
+            logging::messages::bulk_deleted(
+                &context,
+                &gid,
+                &channel_id,
+                &multiple_deleted_messages_ids,
+            )
+            .await;
+        }
+    }
+
+    async fn guild_member_addition(&self, context: Context, new_member: Member)
+    {
+        logging::members::joined(&context, &new_member).await;
+
+        // Members who join during a raid are dealt with by raid mode
+        if automod::raid::member_joined(&context, &new_member).await {
+            return;
+        }
+
+        // Keep out accounts that don't meet the guild's requirements
+        if !new_member.user.bot && automod::gate::member_joined(&context, &new_member).await {
+            return;
+        }
+
+        // Screen the names of new members
+        if !new_member.user.bot {
+            automod::names::check(
+                &context,
+                &new_member.guild_id,
+                &new_member.user,
+                new_member.nick.as_deref(),
+            )
+            .await;
+        }
+    }
+
+    async fn guild_member_removal(&self, context: Context, guild_id: GuildId, user: User)
+    {
+        logging::members::left(&context, &guild_id, &user).await;
+    }
+
+    async fn guild_member_update(&self, context: Context, event: GuildMemberUpdateEvent)
+    {
+        logging::members::updated(&context, &event).await;
+
+        // Screen names again when members change them
+        if !event.user.bot {
+            automod::names::check(
+                &context,
+                &event.guild_id,
+                &event.user,
+                event.nick.as_deref(),
+            )
+            .await;
+        }
+    }
+
+    async fn guild_create(&self, _context: Context, guild: Guild)
+    {
+        // Remember the members the gateway sends, so their leaves and changes
+        // can be logged
+        for (uid, member) in &guild.members {
+            cache::insert_member(guild.id, *uid, member.into());
+        }
+    }
 
     async fn ready(&self, context: Context, ready: Ready)
     {
@@ -272,6 +442,8 @@ impl EventHandler for Handler
                 .create_application_command(|command| builtins::moderation::register(command))
                 .create_application_command(|command| builtins::meta::register(command))
                 .create_application_command(|command| builtins::settings::register(command))
+                .create_application_command(|command| builtins::automod::register(command))
+                .create_application_command(|command| builtins::owner::register(command))
                 .create_application_command(|command| builtins::random::register(command))
                 .create_application_command(|command| conversions::register(command))
                 .create_application_command(|command| wiki::register(command))
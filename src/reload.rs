@@ -0,0 +1,95 @@
+//! Reloading the restricted words and config while the bot runs
+//!
+//! The token and the paths of stored data are only read at startup. The
+//! restricted word list and the paths of the restricted word and phishing
+//! domain lists are reloaded on SIGHUP or with the `reload` command.
+
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use log::{error, info};
+
+use crate::{
+    automod,
+    config::{Config, Resources, RestrictedWords},
+    filtering, CONFIG,
+};
+
+lazy_static! {
+    static ref LIVE: RwLock<Arc<Live>> = RwLock::new(Arc::new(Live {
+        resources: CONFIG.resources.clone(),
+        restricted_words: RestrictedWords::from(CONFIG.resources.restricted_words.clone()).unwrap(),
+    }));
+}
+
+/// The configuration that can be reloaded
+pub struct Live
+{
+    pub resources: Resources,
+    pub restricted_words: RestrictedWords,
+}
+
+/// The current reloadable configuration
+pub fn get() -> Arc<Live> { LIVE.read().unwrap().clone() }
+
+/// Reload the config file and the restricted word list. Nothing changes if
+/// either fails to load. Returns a summary of what was loaded.
+pub fn reload() -> Result<String, String>
+{
+    let path = crate::config_file();
+    let config = Config::from(path.clone())
+        .map_err(|e| format!("Couldn't load config '{}': {e}", path.display()))?;
+    let words = RestrictedWords::from(config.resources.restricted_words.clone()).map_err(|e| {
+        format!(
+            "Couldn't load restricted words '{}': {e}",
+            config.resources.restricted_words.display()
+        )
+    })?;
+
+    let invalid: Vec<String> = words
+        .words
+        .iter()
+        .filter_map(|w| {
+            filtering::validate(w)
+                .err()
+                .map(|e| format!("'{}': {e}", w.pattern()))
+        })
+        .collect();
+
+    let mut summary = format!(
+        "Loaded {} restricted words and {} allowed words",
+        words.words.len(),
+        words.allowed.len()
+    );
+
+    // Swap both at once, so nothing sees the new config with the old list
+    *LIVE.write().unwrap() = Arc::new(Live {
+        resources: config.resources.clone(),
+        restricted_words: words,
+    });
+    filtering::clear();
+
+    match automod::links::reload_phishing() {
+        Ok(count) => summary.push_str(&format!(", and {count} phishing domains")),
+        Err(e) => error!("Couldn't load phishing domains: {e}"),
+    }
+    summary.push('.');
+
+    if !invalid.is_empty() {
+        summary.push_str(&format!(
+            "\nSkipped invalid restricted words: {}",
+            invalid.join(", ")
+        ));
+    }
+
+    // Everything else is only read at startup
+    let mut startup = config.resources.clone();
+    startup.restricted_words = CONFIG.resources.restricted_words.clone();
+    startup.phishing_domains = CONFIG.resources.phishing_domains.clone();
+    if startup != CONFIG.resources || config.secrets != CONFIG.secrets {
+        summary.push_str("\nThe token and data paths only change after a restart.");
+    }
+
+    info!("Reloaded configuration: {summary}");
+    Ok(summary)
+}
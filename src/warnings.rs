@@ -0,0 +1,453 @@
+//! Stored warnings
+//!
+//! Warnings used to be kept by the `bor_warn` crate, which didn't record when
+//! a warning was given. Its files are converted when they're loaded, reading
+//! them with `bor_warn` itself. Their warnings have no issue date, so they
+//! never expire. A file that neither format can read isn't touched, and fails
+//! to load instead.
+//!
+//! Each warning has an ID, unique within its guild. Removed warnings are
+//! kept, with who removed them and why, and edits keep the old reason.
+
+use std::{
+    fs::{self, create_dir_all, read_to_string},
+    io::{Error, ErrorKind},
+    path::PathBuf,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serenity::model::{
+    prelude::{GuildId, UserId},
+    timestamp::Timestamp,
+};
+
+use crate::builtins::moderation::timeout::TimeoutTime;
+
+/// The most points a warning, warning preset or escalation step can have
+pub const MAX_POINTS: u32 = 1_000;
+
+/// Every guild's warnings
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Warnings
+{
+    #[serde(default)]
+    pub guilds: Vec<GuildWarnings>,
+}
+
+/// A guild's warnings
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GuildWarnings
+{
+    pub gid: GuildId,
+
+    /// The ID of the guild's next warning
+    #[serde(default = "_d_next_id")]
+    pub next_id: u64,
+
+    #[serde(default)]
+    pub users: Vec<UserWarnings>,
+}
+
+/// A member's warnings
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UserWarnings
+{
+    pub user: UserId,
+
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+}
+
+/// A single warning
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Warning
+{
+    /// The warning's ID within its guild. Zero until one is given.
+    #[serde(default)]
+    pub id: u64,
+
+    pub reason: String,
+
+    /// How much the warning counts towards the escalation ladder
+    #[serde(default = "_d_points")]
+    pub points: u32,
+
+    /// When the warning was given. Unknown for converted warnings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued: Option<Timestamp>,
+
+    /// Who gave the warning. Unknown for converted warnings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderator: Option<String>,
+
+    /// Who removed the warning and why, if it was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<Change>,
+
+    /// The warning's earlier reasons, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edits: Vec<Change>,
+}
+
+/// A change made to a warning
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Change
+{
+    /// Who made the change
+    pub by: String,
+    pub at: Timestamp,
+
+    /// Why a warning was removed, or its reason before an edit
+    pub reason: String,
+}
+
+impl Warning
+{
+    /// Whether a warning is older than a guild's expiry period. A period of
+    /// zero never expires warnings, and neither do warnings of unknown age.
+    pub fn is_past(&self, expiry: TimeoutTime) -> bool
+    {
+        let expiry = expiry.duration().num_seconds();
+        match self.issued {
+            Some(issued) if expiry > 0 => {
+                Timestamp::now().unix_timestamp() - issued.unix_timestamp() >= expiry
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the warning counts towards the escalation ladder, given a
+    /// guild's expiry period
+    pub fn is_active(&self, expiry: TimeoutTime) -> bool
+    {
+        self.removed.is_none() && !self.is_past(expiry)
+    }
+}
+
+impl Warnings
+{
+    /// Load the warnings from disk, converting files from `bor_warn`
+    pub fn load(path: PathBuf) -> Result<Self, Error>
+    {
+        let contents = read_to_string(&path)?;
+        let mut warnings: Self = match toml::from_str(&contents) {
+            Ok(x) => x,
+            Err(e) => {
+                let legacy = Self::from_legacy(&path).map_err(|legacy| {
+                    Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "Couldn't read warnings from '{}': {e}. They couldn't be converted \
+                             from the old format either: {legacy}",
+                            path.display()
+                        ),
+                    )
+                })?;
+
+                // Keep the old file, in case the conversion missed something
+                let backup = path.with_extension("toml.old");
+                fs::copy(&path, &backup)?;
+                warn!(
+                    "Converted old warnings from '{}', the original is at '{}'",
+                    path.display(),
+                    backup.display()
+                );
+                legacy.save(path.clone())?;
+                legacy
+            }
+        };
+
+        // Warnings saved before IDs existed get them now
+        if warnings.assign_ids() {
+            warnings.save(path.clone())?;
+        }
+
+        info!("Loaded warnings from '{}'", path.display());
+        Ok(warnings)
+    }
+
+    pub fn save(&self, path: PathBuf) -> Result<(), Error>
+    {
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                create_dir_all(parent)?;
+            }
+        }
+
+        let serialized = toml::to_string(&self).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        fs::write(path, serialized)
+    }
+
+    /// Convert warnings saved by `bor_warn`, reading them with its own types
+    fn from_legacy(path: &PathBuf) -> Result<Self, String>
+    {
+        let legacy = bor_warn::warnings::Warnings::load(path).map_err(|e| format!("{e:?}"))?;
+
+        let mut warnings = Self::default();
+        for guild in legacy.guilds {
+            // `bor_warn` compares guild IDs by their text
+            let gid = match guild.id.to_string().parse() {
+                Ok(x) => GuildId(x),
+                Err(_) => return Err(format!("The guild ID '{}' isn't a number", guild.id)),
+            };
+
+            let users = guild
+                .users
+                .into_iter()
+                .map(|user| {
+                    UserWarnings {
+                        user: user.user.id,
+                        warnings: user
+                            .warnings
+                            .iter()
+                            .map(|reason| {
+                                Warning {
+                                    id: 0,
+                                    reason: reason.to_string(),
+                                    points: _d_points(),
+                                    issued: None,
+                                    moderator: None,
+                                    removed: None,
+                                    edits: Vec::new(),
+                                }
+                            })
+                            .collect(),
+                    }
+                })
+                .collect();
+            warnings.guilds.push(GuildWarnings {
+                gid,
+                next_id: _d_next_id(),
+                users,
+            });
+        }
+
+        Ok(warnings)
+    }
+
+    /// A member's warnings, if they have any
+    pub fn get(&self, gid: &GuildId, uid: &UserId) -> Option<&UserWarnings>
+    {
+        self.guilds
+            .iter()
+            .find(|g| g.gid == *gid)?
+            .users
+            .iter()
+            .find(|u| u.user == *uid)
+    }
+
+    /// Give a member a warning. Returns its ID and the member's warnings.
+    pub fn add(&mut self, gid: &GuildId, uid: &UserId, mut warning: Warning)
+        -> (u64, &UserWarnings)
+    {
+        let guild = self.guild_mut(gid);
+        let id = guild.next_id;
+        guild.next_id += 1;
+        warning.id = id;
+
+        let user = guild.user_mut(uid);
+        user.warnings.push(warning);
+        (id, user)
+    }
+
+    /// A guild's warning by its ID, and the member it was given to
+    pub fn find_mut(&mut self, gid: &GuildId, id: u64) -> Option<(UserId, &mut Warning)>
+    {
+        self.guilds
+            .iter_mut()
+            .find(|g| g.gid == *gid)?
+            .users
+            .iter_mut()
+            .find_map(|u| {
+                let user = u.user;
+                u.warnings
+                    .iter_mut()
+                    .find(|w| w.id == id)
+                    .map(|w| (user, w))
+            })
+    }
+
+    /// A member's warnings, creating an empty record if they have none
+    pub fn get_mut(&mut self, gid: &GuildId, uid: &UserId) -> &mut UserWarnings
+    {
+        self.guild_mut(gid).user_mut(uid)
+    }
+
+    /// A guild's warnings, creating an empty record if it has none
+    fn guild_mut(&mut self, gid: &GuildId) -> &mut GuildWarnings
+    {
+        match self.guilds.iter().position(|g| g.gid == *gid) {
+            Some(i) => &mut self.guilds[i],
+            None => {
+                self.guilds.push(GuildWarnings {
+                    gid: *gid,
+                    next_id: _d_next_id(),
+                    users: Vec::new(),
+                });
+                self.guilds.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Give every warning without an ID one. Returns whether any were given.
+    fn assign_ids(&mut self) -> bool
+    {
+        let mut changed = false;
+        for guild in &mut self.guilds {
+            for warning in guild.users.iter_mut().flat_map(|u| u.warnings.iter_mut()) {
+                if warning.id == 0 {
+                    warning.id = guild.next_id;
+                    guild.next_id += 1;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl GuildWarnings
+{
+    /// A member's warnings, creating an empty record if they have none
+    fn user_mut(&mut self, uid: &UserId) -> &mut UserWarnings
+    {
+        match self.users.iter().position(|u| u.user == *uid) {
+            Some(i) => &mut self.users[i],
+            None => {
+                self.users.push(UserWarnings {
+                    user: *uid,
+                    warnings: Vec::new(),
+                });
+                self.users.last_mut().unwrap()
+            }
+        }
+    }
+}
+
+impl UserWarnings
+{
+    /// The warnings that still count towards the escalation ladder, given a
+    /// guild's expiry period
+    pub fn active(&self, expiry: TimeoutTime) -> impl Iterator<Item = &Warning>
+    {
+        self.warnings.iter().filter(move |w| w.is_active(expiry))
+    }
+
+    /// The points of the active warnings
+    pub fn points(&self, expiry: TimeoutTime) -> u32
+    {
+        self.active(expiry)
+            .fold(0, |total, w| total.saturating_add(w.points))
+    }
+}
+
+/// Warning points given to a command
+pub fn parse_points(points: i64) -> Result<u32, String>
+{
+    u32::try_from(points)
+        .ok()
+        .filter(|p| (1..=MAX_POINTS).contains(p))
+        .ok_or_else(|| format!("Warning points must be from 1 to {MAX_POINTS}"))
+}
+
+fn _d_next_id() -> u64 { 1 }
+fn _d_points() -> u32 { 1 }
+
+#[cfg(test)]
+mod tests
+{
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn warning(issued: Option<Timestamp>) -> Warning
+    {
+        Warning {
+            id: 0,
+            reason: "Testing".to_string(),
+            points: 1,
+            issued,
+            moderator: None,
+            removed: None,
+            edits: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_unreadable_file()
+    {
+        let path = std::env::temp_dir().join(format!("bor-warnings-{}.toml", std::process::id()));
+        let contents = "[[guilds]]\nthis isn't = warnings";
+        fs::write(&path, contents).unwrap();
+
+        // Neither format reads it, so it's left as it was
+        assert!(Warnings::load(path.clone()).is_err());
+        assert_eq!(read_to_string(&path).unwrap(), contents);
+        assert!(!path.with_extension("toml.old").exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_expiry()
+    {
+        let day = TimeoutTime::from_str("1d").unwrap();
+        let old = Timestamp::now().unix_timestamp() - 2 * 86_400;
+        let old = Timestamp::from_unix_timestamp(old).unwrap();
+
+        assert!(warning(Some(old)).is_past(day));
+        assert!(!warning(Some(Timestamp::now())).is_past(day));
+        assert!(!warning(Some(old)).is_past(TimeoutTime::default()));
+        assert!(!warning(None).is_past(day));
+
+        let gid = GuildId(1);
+        let mut warnings = Warnings::default();
+        warnings.add(&gid, &UserId(2), warning(Some(old)));
+        warnings.add(&gid, &UserId(2), warning(None));
+        let user = warnings.get(&gid, &UserId(2)).unwrap();
+        assert_eq!(user.points(day), 1);
+
+        // Changing the period changes which warnings count, both ways
+        assert_eq!(user.points(TimeoutTime::from_str("3d").unwrap()), 2);
+        assert_eq!(user.points(TimeoutTime::default()), 2);
+        assert_eq!(user.points(TimeoutTime::from_str("1h").unwrap()), 1);
+    }
+
+    #[test]
+    fn test_ids()
+    {
+        let (first, second) = (GuildId(1), GuildId(2));
+        let mut warnings = Warnings::default();
+        assert_eq!(warnings.add(&first, &UserId(3), warning(None)).0, 1);
+        assert_eq!(warnings.add(&first, &UserId(4), warning(None)).0, 2);
+        assert_eq!(warnings.add(&second, &UserId(3), warning(None)).0, 1);
+
+        let (user, found) = warnings.find_mut(&first, 2).unwrap();
+        assert_eq!(user, UserId(4));
+        found.points = 3;
+        let user = warnings.get(&first, &UserId(4)).unwrap();
+        assert_eq!(user.points(TimeoutTime::default()), 3);
+        assert!(warnings.find_mut(&second, 2).is_none());
+    }
+
+    #[test]
+    fn test_points()
+    {
+        assert_eq!(parse_points(1), Ok(1));
+        assert_eq!(parse_points(MAX_POINTS.into()), Ok(MAX_POINTS));
+        assert!(parse_points(0).is_err());
+        assert!(parse_points(-1).is_err());
+        assert!(parse_points(i64::from(MAX_POINTS) + 1).is_err());
+        assert!(parse_points(i64::from(u32::MAX) + 1).is_err());
+
+        // Points stored in the file aren't bound by the commands' limit
+        let mut heavy = warning(None);
+        heavy.points = u32::MAX;
+        let (gid, uid) = (GuildId(1), UserId(2));
+        let mut warnings = Warnings::default();
+        warnings.add(&gid, &uid, heavy.clone());
+        warnings.add(&gid, &uid, heavy);
+        let user = warnings.get(&gid, &uid).unwrap();
+        assert_eq!(user.points(TimeoutTime::default()), u32::MAX);
+    }
+}